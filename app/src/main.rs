@@ -1,17 +1,90 @@
-use renki_core::RenkiCore;
-use std::{env, fs};
+use renki_core::{Background, Dither, FrameRate, GeneratorOptions, ImageDurations, MotionStyle, OutputFormat, RenderOptions, RenkiCore, Resampler, Scenario, Transition};
+use std::path::Path;
+use std::env;
+
+fn parse_seconds(value: Option<String>) -> f64 {
+    value.expect("Missing value").parse::<f64>().expect("Invalid number of seconds")
+}
 
 fn main() {
-    let args: Vec<String> = env::args().collect();
-    let path = args[1].clone();
-    let paths = fs::read_dir(&path).expect("Failed to scan directory");
-    let mut filenames = Vec::new();
-    for path in paths {
-        if path.is_ok() {
-            filenames.push(path.unwrap().path().to_str().unwrap().to_string());
+    let mut scenario_path = None;
+    let mut export_path = None;
+    let mut options = GeneratorOptions::default();
+    let mut render_options = RenderOptions::default();
+    let mut durations = None;
+    let mut weighted = false;
+    let mut styles = Vec::new();
+    let mut preset = None;
+    let mut presets_path = None;
+    let mut positional = Vec::new();
+    let mut args = env::args().skip(1);
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--scenario" => scenario_path = Some(args.next().expect("Missing scenario file")),
+            "--export" => export_path = Some(args.next().expect("Missing export file")),
+            "--fps" => options.fps = FrameRate::from_fps(args.next().expect("Missing fps").parse::<f64>().expect("Invalid fps"))
+                .expect("Invalid fps"),
+            "--overlap" => options.overlap = parse_seconds(args.next()),
+            "--lead-in" => options.lead_in = parse_seconds(args.next()),
+            "--lead-out" => options.lead_out = parse_seconds(args.next()),
+            "--image-duration" => durations = Some(ImageDurations::Each(parse_seconds(args.next()))),
+            "--durations" => durations = Some(ImageDurations::List(args.next().expect("Missing durations")
+                .split(',').map(|duration| duration.trim().parse::<f64>().expect("Invalid duration")).collect())),
+            "--durations-from" => durations = Some(ImageDurations::List(
+                Scenario::load(&args.next().expect("Missing scenario file")).expect("Failed to load scenario").image_durations())),
+            "--focus-from" => options.focus =
+                RenkiCore::focus_regions(&args.next().expect("Missing scenario file")).expect("Failed to load scenario"),
+            "--weighted" => weighted = true,
+            "--no-cover" => options.cover_frame = false,
+            "--no-saliency" => options.saliency = false,
+            "--background" => options.background =
+                args.next().expect("Missing background").parse::<Background>().expect("Invalid background"),
+            "--transition" => options.transition =
+                Some(args.next().expect("Missing transition").parse::<Transition>().expect("Invalid transition")),
+            "--resampler" => render_options.resampler =
+                args.next().expect("Missing resampler").parse::<Resampler>().expect("Invalid resampler"),
+            "--no-mipmaps" => render_options.mipmaps = false,
+            "--threads" => render_options.threads = args.next().expect("Missing thread count").parse::<usize>().expect("Invalid thread count"),
+            "--tiles" => render_options.tiles = true,
+            "--no-linear" => render_options.linear = false,
+            "--transparent" => render_options.transparent = true,
+            "--dither" => render_options.dither =
+                args.next().expect("Missing dither").parse::<Dither>().expect("Invalid dither"),
+            "--output-format" => render_options.output =
+                args.next().expect("Missing output format").parse::<OutputFormat>().expect("Invalid output format"),
+            "--seed" => options.seed = Some(args.next().expect("Missing seed").parse::<u64>().expect("Invalid seed")),
+            "--preset" => preset = Some(args.next().expect("Missing preset")),
+            "--presets" => presets_path = Some(args.next().expect("Missing presets file")),
+            "--style" => {
+                let style = args.next().expect("Missing style");
+                let (name, style) = style.split_once('=').expect("Style must be given as image=style");
+                styles.push((name.to_string(), style.parse::<MotionStyle>().expect("Invalid style")));
+            }
+            _ => positional.push(arg),
         }
     }
+
+    if let Some(preset) = preset {
+        options.preset = RenkiCore::preset(&preset, presets_path.as_deref()).expect("Failed to load preset");
+    }
+    if let Some(scenario_path) = scenario_path {
+        RenkiCore::render_scenario(&scenario_path, &render_options).expect("Failed to render scenario");
+        return;
+    }
+    let path = positional[0].clone();
+    let filenames = RenkiCore::scan_images(&path).expect("Failed to scan directory");
+    for (name, style) in styles {
+        let filename = Path::new(&path).join(name).to_str().unwrap().to_string();
+        options.styles.insert(filename, style);
+    }
     println!("Found {} images", filenames.len());
-    let scenario_len = args[2].parse::<usize>().unwrap();
-    RenkiCore::render_images(&filenames, scenario_len);
+    options.durations = durations.unwrap_or_else(|| {
+        let duration = positional[1].parse::<f64>().expect("Invalid duration");
+        if weighted { ImageDurations::Weighted(duration) } else { ImageDurations::Total(duration) }
+    });
+    match export_path {
+        Some(export_path) => RenkiCore::export_scenario(&filenames, &options, &export_path)
+            .expect("Failed to export scenario"),
+        None => RenkiCore::render_images(&filenames, &options, &render_options).expect("Failed to render images"),
+    }
 }
@@ -0,0 +1,230 @@
+use std::collections::HashMap;
+use std::str::FromStr;
+use serde::{Deserialize, Serialize};
+use crate::color::color_to_linear;
+use crate::matrix::Matrix2d;
+use crate::renki_image::RenkiImage;
+
+/// Fill of the frame areas the images do not cover. Colors are `[r, g, b]` from 0 to 255.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum Background {
+    Solid {
+        #[serde(default)]
+        color: [f32; 3],
+    },
+    /// Linear gradient across the frame, `angle` is its direction in radians, 0 from left to right
+    /// and pi/2 from top to bottom.
+    Gradient {
+        from: [f32; 3],
+        to: [f32; 3],
+        #[serde(default = "default_gradient_angle")]
+        angle: f64,
+    },
+    /// Each image blurred and scaled up to cover the frame is drawn under it with the image opacity.
+    /// `radius` is the blur radius in frame pixels, `brightness` darkens the blurred copy.
+    Blur {
+        #[serde(default = "default_blur_radius")]
+        radius: f64,
+        #[serde(default = "default_blur_brightness")]
+        brightness: f32,
+    },
+    /// Image scaled to cover the frame, the path is resolved like the scenario image paths.
+    Image {
+        image: String,
+    },
+}
+
+impl Default for Background {
+    fn default() -> Self {
+        Background::Solid { color: [0.0; 3] }
+    }
+}
+
+fn default_gradient_angle() -> f64 {
+    std::f64::consts::FRAC_PI_2
+}
+
+fn default_blur_radius() -> f64 {
+    40.0
+}
+
+fn default_blur_brightness() -> f32 {
+    0.7
+}
+
+/// Background images of a scenario prepared for rendering: `base` fills every frame,
+/// `pads` holds the blurred copies of the images for [`Background::Blur`] at an eighth of the frame size.
+pub(crate) struct BackgroundLayers {
+    pub base: RenkiImage,
+    pub pads: HashMap<String, RenkiImage>,
+}
+
+impl BackgroundLayers {
+    /// Blurred copy of the image scaled up to the frame with opacity `alpha`, `None` without a blur background.
+    pub fn pad(&self, image: &str, alpha: f64) -> Option<RenkiImage> {
+        let pad = self.pads.get(image)?;
+        let (width, height) = (self.base.width, self.base.height);
+        let channels = pad.channels.iter().map(|channel| upscale(channel, pad.width, pad.height, width, height)).collect();
+        Some(RenkiImage::new(width, height, channels, vec![alpha as f32; width * height]))
+    }
+}
+
+/// The image scaled to cover a `width` x `height` frame around its center.
+fn cover(image: &RenkiImage, width: usize, height: usize) -> RenkiImage {
+    let scale = (width as f64 / image.width as f64).max(height as f64 / image.height as f64);
+    let matrix = Matrix2d::translate(-(image.width as f64) * 0.5, -(image.height as f64) * 0.5)
+        .multiply(&Matrix2d::scale(scale))
+        .multiply(&Matrix2d::translate(width as f64 * 0.5, height as f64 * 0.5));
+    let (source, matrix) = image.mipmap_level(matrix);
+    let mut covered = source.transform(&matrix, width, height, 1.0);
+    covered.alpha = vec![1_f32; width * height];
+    covered
+}
+
+/// Box blur along rows, repeated three times it approximates a gaussian blur.
+fn box_blur_rows(channel: &[f32], width: usize, height: usize, radius: usize) -> Vec<f32> {
+    let mut result = vec![0_f32; channel.len()];
+    let window = (2 * radius + 1) as f32;
+    for y in 0..height {
+        let row = &channel[y * width..(y + 1) * width];
+        let sample = |x: isize| row[x.clamp(0, width as isize - 1) as usize];
+        let mut sum: f32 = (-(radius as isize)..=radius as isize).map(sample).sum();
+        for x in 0..width {
+            result[y * width + x] = sum / window;
+            sum += sample(x as isize + radius as isize + 1) - sample(x as isize - radius as isize);
+        }
+    }
+    result
+}
+
+fn transpose(channel: &[f32], width: usize, height: usize) -> Vec<f32> {
+    let mut result = vec![0_f32; channel.len()];
+    for y in 0..height {
+        for x in 0..width {
+            result[x * height + y] = channel[y * width + x];
+        }
+    }
+    result
+}
+
+fn blur(channel: &[f32], width: usize, height: usize, radius: usize) -> Vec<f32> {
+    let mut channel = channel.to_vec();
+    for _pass in 0..3 {
+        channel = box_blur_rows(&channel, width, height, radius);
+    }
+    channel = transpose(&channel, width, height);
+    for _pass in 0..3 {
+        channel = box_blur_rows(&channel, height, width, radius);
+    }
+    transpose(&channel, height, width)
+}
+
+/// Bilinear upscale of a channel to `width` x `height`.
+fn upscale(channel: &[f32], source_width: usize, source_height: usize, width: usize, height: usize) -> Vec<f32> {
+    let mut result = Vec::with_capacity(width * height);
+    for y in 0..height {
+        let source_y = ((y as f64 + 0.5) * source_height as f64 / height as f64 - 0.5).clamp(0.0, source_height as f64 - 1.0);
+        let (y0, fy) = (source_y.floor() as usize, (source_y - source_y.floor()) as f32);
+        let y1 = (y0 + 1).min(source_height - 1);
+        for x in 0..width {
+            let source_x = ((x as f64 + 0.5) * source_width as f64 / width as f64 - 0.5).clamp(0.0, source_width as f64 - 1.0);
+            let (x0, fx) = (source_x.floor() as usize, (source_x - source_x.floor()) as f32);
+            let x1 = (x0 + 1).min(source_width - 1);
+            let top = channel[y0 * source_width + x0] * (1.0 - fx) + channel[y0 * source_width + x1] * fx;
+            let bottom = channel[y1 * source_width + x0] * (1.0 - fx) + channel[y1 * source_width + x1] * fx;
+            result.push(top * (1.0 - fy) + bottom * fy);
+        }
+    }
+    result
+}
+
+/// Blurred copy of the image covering an eighth of the `width` x `height` frame, scaled up when drawn.
+fn blur_pad(image: &RenkiImage, width: usize, height: usize, radius: f64, brightness: f32) -> RenkiImage {
+    let (small_width, small_height) = (width.div_ceil(8), height.div_ceil(8));
+    let small = cover(image, small_width, small_height);
+    let small_radius = (radius / 8.0).round().max(1.0) as usize;
+    let channels = small.channels.iter()
+        .map(|channel| blur(channel, small_width, small_height, small_radius).iter().map(|value| value * brightness).collect())
+        .collect();
+    RenkiImage::new(small_width, small_height, channels, vec![1_f32; small_width * small_height])
+}
+
+impl Background {
+    pub fn is_black(&self) -> bool {
+        *self == Background::default()
+    }
+
+    /// Background image file, loaded with the scenario images.
+    pub fn image(&self) -> Option<&str> {
+        match self {
+            Background::Image { image } => Some(image),
+            _ => None,
+        }
+    }
+
+    pub fn validate(&self) -> Result<(), String> {
+        match self {
+            Background::Gradient { angle, .. } if !angle.is_finite() => Err(format!("invalid gradient angle {}", angle)),
+            Background::Blur { radius, brightness } if !radius.is_finite() || *radius < 0.0 || !(0.0..=1.0).contains(brightness) => {
+                Err(format!("invalid blur radius {} or brightness {}", radius, brightness))
+            }
+            Background::Image { image } if image.is_empty() => Err(String::from("background image path is empty")),
+            _ => Ok(()),
+        }
+    }
+
+    /// Prepares the background of a `width` x `height` frame for the `images` of a scenario.
+    /// With `linear` the colors are converted to linear light like the images.
+    pub(crate) fn prepare(&self, width: usize, height: usize, images: &[String],
+                          images_map: &HashMap<String, RenkiImage>, linear: bool) -> Result<BackgroundLayers, String> {
+        let size = width * height;
+        let decode = |color: &[f32; 3]| if linear { color_to_linear(color) } else { *color };
+        let solid = |color: &[f32; 3]| decode(color).iter().map(|value| vec![*value; size]).collect();
+        let channels = match self {
+            Background::Solid { color } => solid(color),
+            Background::Blur { .. } => solid(&[0.0; 3]),
+            Background::Gradient { from, to, angle } => {
+                let (from, to) = (decode(from), decode(to));
+                let (dx, dy) = (angle.cos(), angle.sin());
+                let extent = (width as f64 * 0.5 * dx).abs() + (height as f64 * 0.5 * dy).abs();
+                let mut channels: Vec<Vec<f32>> = (0..3).map(|_| Vec::with_capacity(size)).collect();
+                for y in 0..height {
+                    for x in 0..width {
+                        let along = (x as f64 + 0.5 - width as f64 * 0.5) * dx + (y as f64 + 0.5 - height as f64 * 0.5) * dy;
+                        let t = ((along / extent + 1.0) * 0.5).clamp(0.0, 1.0) as f32;
+                        for (channel_index, channel) in channels.iter_mut().enumerate() {
+                            channel.push(from[channel_index] * (1.0 - t) + to[channel_index] * t);
+                        }
+                    }
+                }
+                channels
+            }
+            Background::Image { image } => {
+                cover(images_map.get(image).ok_or_else(|| format!("background image {} is not loaded", image))?, width, height).channels
+            }
+        };
+        let mut pads = HashMap::new();
+        if let Background::Blur { radius, brightness } = self {
+            for image in images {
+                if !pads.contains_key(image) {
+                    let source = images_map.get(image).ok_or_else(|| format!("image {} is not loaded", image))?;
+                    pads.insert(image.clone(), blur_pad(source, width, height, *radius, *brightness));
+                }
+            }
+        }
+        Ok(BackgroundLayers { base: RenkiImage::new(width, height, channels, vec![1_f32; size]), pads })
+    }
+}
+
+impl FromStr for Background {
+    type Err = String;
+
+    /// A background as JSON, or the name of a background type with default parameters, e.g. `blur`.
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        let json = if value.trim_start().starts_with('{') { value.to_string() } else { format!("{{\"type\": \"{}\"}}", value) };
+        let background: Background = serde_json::from_str(&json).map_err(|e| format!("invalid background {}: {}", value, e))?;
+        background.validate()?;
+        Ok(background)
+    }
+}
@@ -0,0 +1,33 @@
+use crate::renki_image::RenkiImage;
+
+/// Linear light value of an sRGB encoded channel value, both from 0 to 255.
+pub(crate) fn srgb_to_linear(value: f32) -> f32 {
+    let encoded = (value / 255.0).clamp(0.0, 1.0);
+    let linear = if encoded <= 0.04045 { encoded / 12.92 } else { ((encoded + 0.055) / 1.055).powf(2.4) };
+    linear * 255.0
+}
+
+/// sRGB encoded value of a linear light channel value, both from 0 to 255.
+pub(crate) fn linear_to_srgb(value: f32) -> f32 {
+    let linear = (value / 255.0).clamp(0.0, 1.0);
+    let encoded = if linear <= 0.0031308 { linear * 12.92 } else { 1.055 * linear.powf(1.0 / 2.4) - 0.055 };
+    encoded * 255.0
+}
+
+pub(crate) fn color_to_linear(color: &[f32; 3]) -> [f32; 3] {
+    color.map(srgb_to_linear)
+}
+
+impl RenkiImage {
+    /// Copy of an sRGB encoded image in linear light.
+    pub fn to_linear(&self) -> RenkiImage {
+        let channels = self.channels.iter().map(|channel| channel.iter().map(|value| srgb_to_linear(*value)).collect()).collect();
+        RenkiImage::new(self.width, self.height, channels, self.alpha.clone())
+    }
+
+    /// Copy of a linear light image encoded to sRGB.
+    pub fn to_srgb(&self) -> RenkiImage {
+        let channels = self.channels.iter().map(|channel| channel.iter().map(|value| linear_to_srgb(*value)).collect()).collect();
+        RenkiImage::new(self.width, self.height, channels, self.alpha.clone())
+    }
+}
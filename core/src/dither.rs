@@ -0,0 +1,195 @@
+//! Dithering of frames quantized to 8 bits. The threshold patterns move with the frame index,
+//! so the dither averages out over time instead of standing still over the frame.
+//!
+//! The blue noise pattern is generated once with the void and cluster method: starting from a relaxed
+//! sparse random pattern, pixels are ranked by removing the tightest cluster and then filling the largest
+//! void, and the rank of a pixel is its threshold.
+
+use std::str::FromStr;
+use std::sync::OnceLock;
+use crate::random::Random;
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Dither {
+    /// Rounding to the nearest level.
+    #[default]
+    None,
+    /// 8x8 Bayer matrix.
+    Ordered,
+    /// 64x64 blue noise pattern.
+    BlueNoise,
+}
+
+const BAYER_SIZE: usize = 8;
+const BLUE_NOISE_SIZE: usize = 64;
+/// Standard deviation of the gaussian measuring pixel clusters of the blue noise pattern.
+const BLUE_NOISE_SIGMA: f64 = 1.5;
+/// Fixed seed, the pattern is the same on every run.
+const BLUE_NOISE_SEED: u64 = 0x5EED;
+
+fn bayer() -> Vec<f32> {
+    let mut matrix = vec![0_usize];
+    let mut size = 1;
+    while size < BAYER_SIZE {
+        let mut next = vec![0; size * size * 4];
+        for y in 0..size {
+            for x in 0..size {
+                let value = matrix[y * size + x] * 4;
+                next[y * size * 2 + x] = value;
+                next[y * size * 2 + x + size] = value + 2;
+                next[(y + size) * size * 2 + x] = value + 3;
+                next[(y + size) * size * 2 + x + size] = value + 1;
+            }
+        }
+        matrix = next;
+        size *= 2;
+    }
+    thresholds(&matrix)
+}
+
+/// Ranks `0..n` mapped to offsets in `-0.5..0.5`.
+fn thresholds(ranks: &[usize]) -> Vec<f32> {
+    ranks.iter().map(|rank| ((*rank as f64 + 0.5) / ranks.len() as f64 - 0.5) as f32).collect()
+}
+
+struct VoidAndCluster {
+    pattern: Vec<bool>,
+    energy: Vec<f64>,
+    kernel: Vec<f64>,
+}
+
+impl VoidAndCluster {
+    fn new(pattern: Vec<bool>) -> VoidAndCluster {
+        let size = BLUE_NOISE_SIZE;
+        let mut kernel = vec![0.0; size * size];
+        for dy in 0..size {
+            for dx in 0..size {
+                let (wrapped_x, wrapped_y) = (dx.min(size - dx) as f64, dy.min(size - dy) as f64);
+                kernel[dy * size + dx] = (-(wrapped_x * wrapped_x + wrapped_y * wrapped_y) / (2.0 * BLUE_NOISE_SIGMA * BLUE_NOISE_SIGMA)).exp();
+            }
+        }
+        let mut state = VoidAndCluster { pattern: vec![false; size * size], energy: vec![0.0; size * size], kernel };
+        for (index, set) in pattern.into_iter().enumerate() {
+            if set {
+                state.set(index, true);
+            }
+        }
+        state
+    }
+
+    fn set(&mut self, index: usize, value: bool) {
+        let size = BLUE_NOISE_SIZE;
+        self.pattern[index] = value;
+        let sign = if value { 1.0 } else { -1.0 };
+        let (x, y) = (index % size, index / size);
+        for (target, energy) in self.energy.iter_mut().enumerate() {
+            let dx = (target % size + size - x) % size;
+            let dy = (target / size + size - y) % size;
+            *energy += sign * self.kernel[dy * size + dx];
+        }
+    }
+
+    /// Set pixel with the most set neighbors.
+    fn tightest_cluster(&self) -> usize {
+        (0..self.pattern.len()).filter(|index| self.pattern[*index])
+            .max_by(|a, b| self.energy[*a].total_cmp(&self.energy[*b])).expect("Empty pattern")
+    }
+
+    /// Unset pixel with the fewest set neighbors.
+    fn largest_void(&self) -> usize {
+        (0..self.pattern.len()).filter(|index| !self.pattern[*index])
+            .min_by(|a, b| self.energy[*a].total_cmp(&self.energy[*b])).expect("Full pattern")
+    }
+}
+
+fn blue_noise() -> Vec<f32> {
+    let count = BLUE_NOISE_SIZE * BLUE_NOISE_SIZE;
+    let mut random = Random::new(BLUE_NOISE_SEED);
+    let mut initial = vec![false; count];
+    let mut ones = 0;
+    while ones < count / 10 {
+        let index = random.below(count);
+        if !initial[index] {
+            initial[index] = true;
+            ones += 1;
+        }
+    }
+    // relax the initial pattern by moving the tightest cluster into the largest void until it stays put
+    let mut state = VoidAndCluster::new(initial);
+    loop {
+        let cluster = state.tightest_cluster();
+        state.set(cluster, false);
+        let void = state.largest_void();
+        state.set(void, true);
+        if void == cluster {
+            break;
+        }
+    }
+    let initial = state.pattern.clone();
+
+    let mut ranks = vec![0; count];
+    for rank in (0..ones).rev() {
+        let cluster = state.tightest_cluster();
+        state.set(cluster, false);
+        ranks[cluster] = rank;
+    }
+    let mut state = VoidAndCluster::new(initial);
+    for rank in ones..count {
+        let void = state.largest_void();
+        state.set(void, true);
+        ranks[void] = rank;
+    }
+    thresholds(&ranks)
+}
+
+impl Dither {
+    pub const ALL: [Dither; 3] = [Dither::None, Dither::Ordered, Dither::BlueNoise];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Dither::None => "none",
+            Dither::Ordered => "ordered",
+            Dither::BlueNoise => "blue_noise",
+        }
+    }
+
+    /// Threshold pattern and its size.
+    fn pattern(&self) -> Option<(&'static [f32], usize)> {
+        static BAYER: OnceLock<Vec<f32>> = OnceLock::new();
+        static BLUE_NOISE: OnceLock<Vec<f32>> = OnceLock::new();
+        match self {
+            Dither::None => None,
+            Dither::Ordered => Some((BAYER.get_or_init(bayer), BAYER_SIZE)),
+            Dither::BlueNoise => Some((BLUE_NOISE.get_or_init(blue_noise), BLUE_NOISE_SIZE)),
+        }
+    }
+
+    /// Quantizes a channel of a `width` pixels wide frame to 8 bits. Every channel and frame reads
+    /// the pattern at another offset, stepping by the R2 low discrepancy sequence.
+    pub(crate) fn quantize(&self, channel: &[f32], width: usize, channel_index: usize, frame_index: usize) -> Vec<u8> {
+        let quantize = |value: f32| value.round().clamp(0.0, 255.0) as u8;
+        let (pattern, size) = match self.pattern() {
+            Some(pattern) => pattern,
+            None => return channel.iter().map(|value| quantize(*value)).collect(),
+        };
+        let step = (frame_index * 3 + channel_index) as f64;
+        let offset_x = ((step * 0.754_877_666_2).fract() * size as f64) as usize;
+        let offset_y = ((step * 0.569_840_291_0).fract() * size as f64) as usize;
+        channel.iter().enumerate()
+            .map(|(index, value)| {
+                let x = (index % width + offset_x) % size;
+                let y = (index / width + offset_y) % size;
+                quantize(value + pattern[y * size + x])
+            })
+            .collect()
+    }
+}
+
+impl FromStr for Dither {
+    type Err = String;
+
+    fn from_str(name: &str) -> Result<Self, Self::Err> {
+        Dither::ALL.iter().find(|dither| dither.name() == name).copied()
+            .ok_or_else(|| format!("unknown dither {}", name))
+    }
+}
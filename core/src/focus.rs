@@ -0,0 +1,58 @@
+use std::fs;
+use std::path::Path;
+use serde::{Deserialize, Serialize};
+
+/// Region of an image that should stay in the frame, in source image pixels.
+/// A region without size is a focus point.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct FocusRegion {
+    pub x: f64,
+    pub y: f64,
+    #[serde(default)]
+    pub width: f64,
+    #[serde(default)]
+    pub height: f64,
+}
+
+impl FocusRegion {
+    pub fn point(x: f64, y: f64) -> FocusRegion {
+        FocusRegion { x, y, width: 0.0, height: 0.0 }
+    }
+
+    pub fn center_x(&self) -> f64 {
+        self.x + self.width * 0.5
+    }
+
+    pub fn center_y(&self) -> f64 {
+        self.y + self.height * 0.5
+    }
+
+    /// Sidecar file of an image: the image path with `.focus.json` appended, e.g. `photo.jpg.focus.json`.
+    pub fn sidecar_path(image_path: &str) -> String {
+        format!("{}.focus.json", image_path)
+    }
+
+    /// Reads the sidecar file of an image, `None` if the image has none.
+    pub fn load_sidecar(image_path: &str) -> Result<Option<FocusRegion>, String> {
+        let path = FocusRegion::sidecar_path(image_path);
+        if !Path::new(&path).exists() {
+            return Ok(None);
+        }
+        let json = fs::read_to_string(&path).map_err(|e| format!("Failed to read focus {}: {}", path, e))?;
+        serde_json::from_str(&json).map(Some).map_err(|e| format!("Invalid focus {}: {}", path, e))
+    }
+
+    pub fn validate(&self, image_width: f64, image_height: f64) -> Result<(), String> {
+        if ![self.x, self.y, self.width, self.height].iter().all(|value| value.is_finite()) {
+            return Err(String::from("focus region has non-finite values"));
+        }
+        if self.width < 0.0 || self.height < 0.0 {
+            return Err(format!("focus region size {}x{} is negative", self.width, self.height));
+        }
+        if self.x < 0.0 || self.y < 0.0 || self.x + self.width > image_width || self.y + self.height > image_height {
+            return Err(format!("focus region {}x{} at {},{} is outside of the {}x{} image",
+                               self.width, self.height, self.x, self.y, image_width, image_height));
+        }
+        Ok(())
+    }
+}
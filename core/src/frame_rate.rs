@@ -0,0 +1,62 @@
+use serde::{Deserialize, Serialize};
+
+/// Frame rate as an exact fraction, written to scenario files as frames per second.
+/// The NTSC rates 23.976, 29.97 and 59.94 are read as 24000/1001, 30000/1001 and 60000/1001.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(try_from = "f64", into = "f64")]
+pub struct FrameRate {
+    pub numerator: u32,
+    pub denominator: u32,
+}
+
+impl FrameRate {
+    pub fn new(numerator: u32, denominator: u32) -> FrameRate {
+        FrameRate { numerator, denominator }
+    }
+
+    pub fn from_fps(fps: f64) -> Result<FrameRate, String> {
+        if !fps.is_finite() || fps <= 0.0 || fps > 1000.0 {
+            return Err(format!("invalid frame rate {}", fps));
+        }
+        if fps.fract() == 0.0 {
+            return Ok(FrameRate::new(fps as u32, 1));
+        }
+        let ntsc = (fps * 1.001).round();
+        if (ntsc / 1.001 - fps).abs() < 0.005 {
+            return Ok(FrameRate::new(ntsc as u32 * 1000, 1001));
+        }
+        Ok(FrameRate::new((fps * 1000.0).round() as u32, 1000))
+    }
+
+    pub fn fps(&self) -> f64 {
+        self.numerator as f64 / self.denominator as f64
+    }
+
+    pub fn frame_time(&self, frame_index: usize) -> f64 {
+        frame_index as f64 * self.denominator as f64 / self.numerator as f64
+    }
+
+    pub fn frame_count(&self, duration: f64) -> usize {
+        (duration * self.fps()).round() as usize
+    }
+}
+
+impl Default for FrameRate {
+    fn default() -> Self {
+        FrameRate::new(25, 1)
+    }
+}
+
+impl TryFrom<f64> for FrameRate {
+    type Error = String;
+
+    fn try_from(fps: f64) -> Result<Self, Self::Error> {
+        FrameRate::from_fps(fps)
+    }
+}
+
+impl From<FrameRate> for f64 {
+    fn from(frame_rate: FrameRate) -> Self {
+        frame_rate.fps()
+    }
+}
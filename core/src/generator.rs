@@ -0,0 +1,474 @@
+use std::collections::HashMap;
+use std::str::FromStr;
+use crate::renki_image::RenkiImage;
+use crate::scenario::{ImageScenario, InterpolationSettings, Scenario, ScenarioPoint, SCENARIO_VERSION};
+use crate::interpolation::Easing;
+use crate::frame_rate::FrameRate;
+use crate::geom::Point;
+use crate::random::Random;
+use crate::preset::MotionPreset;
+use crate::focus::FocusRegion;
+use crate::saliency::salient_region;
+use crate::transition::Transition;
+use crate::background::Background;
+
+/// How long each image is shown, in seconds. The duration of an image includes
+/// the transitions from the previous and to the next image.
+#[derive(Clone, Debug, PartialEq)]
+pub enum ImageDurations {
+    /// Total scenario duration, split evenly between the images.
+    Total(f64),
+    /// The same duration for every image.
+    Each(f64),
+    /// Duration of every image, in the order of the images.
+    List(Vec<f64>),
+    /// Total scenario duration, split in proportion to how far the image aspect ratio is from the frame one,
+    /// so panoramas and portrait photos in a landscape frame get more time for their pan.
+    Weighted(f64),
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct GeneratorOptions {
+    pub width: usize,
+    pub height: usize,
+    pub fps: FrameRate,
+    pub durations: ImageDurations,
+    /// Transition time between consecutive images in seconds.
+    pub overlap: f64,
+    /// Time the first image is held still before its duration starts, it does not fade in.
+    pub lead_in: f64,
+    /// Time the last image is held still after its duration ends, it does not fade out.
+    pub lead_out: f64,
+    /// Raise the scale of keyframes where the rotated image would leave parts of the frame uncovered.
+    /// Disable for intentional letterboxing.
+    pub cover_frame: bool,
+    /// Seed for choosing a random motion style for each image, without a seed the style follows the image shape
+    /// and pans alternate their direction.
+    pub seed: Option<u64>,
+    /// Styles pinned to image files, used instead of the chosen ones.
+    pub styles: HashMap<String, MotionStyle>,
+    pub preset: MotionPreset,
+    /// Regions of the images, keyed by image file, kept in the frame while the image is settled.
+    /// Zooms end on the focus region and pans move towards it.
+    pub focus: HashMap<String, FocusRegion>,
+    /// Estimate the focus region of images without one from the image content.
+    pub saliency: bool,
+    /// Transition used between all consecutive images, without one the images crossfade.
+    pub transition: Option<Transition>,
+    pub background: Background,
+}
+
+impl Default for GeneratorOptions {
+    fn default() -> Self {
+        GeneratorOptions {
+            width: 1080,
+            height: 1920,
+            fps: FrameRate::default(),
+            durations: ImageDurations::Each(5.0),
+            overlap: 1.0,
+            lead_in: 0.0,
+            lead_out: 0.0,
+            cover_frame: true,
+            seed: None,
+            styles: HashMap::new(),
+            preset: MotionPreset::default(),
+            focus: HashMap::new(),
+            saliency: true,
+            transition: None,
+            background: Background::default(),
+        }
+    }
+}
+
+impl GeneratorOptions {
+    fn image_durations(&self, images: &[&RenkiImage]) -> Result<Vec<f64>, String> {
+        let count = images.len() as f64;
+        let shared_duration = |total: f64| {
+            total - self.lead_in - self.lead_out + (count - 1.0) * self.overlap
+        };
+        let durations = match &self.durations {
+            ImageDurations::Total(total) => vec![shared_duration(*total) / count; images.len()],
+            ImageDurations::Each(duration) => vec![*duration; images.len()],
+            ImageDurations::List(durations) => {
+                if durations.len() != images.len() {
+                    return Err(format!("{} durations given for {} images", durations.len(), images.len()));
+                }
+                durations.clone()
+            }
+            ImageDurations::Weighted(total) => {
+                let frame_aspect = self.width as f64 / self.height as f64;
+                let weights: Vec<f64> = images.iter()
+                    .map(|image| {
+                        let relative_aspect = image.width as f64 / image.height as f64 / frame_aspect;
+                        relative_aspect.max(1.0 / relative_aspect)
+                    })
+                    .collect();
+                let weights_sum: f64 = weights.iter().sum();
+                weights.iter().map(|weight| shared_duration(*total) * weight / weights_sum).collect()
+            }
+        };
+        if let Some(duration) = durations.iter().find(|duration| !duration.is_finite() || **duration <= 2.0 * self.overlap) {
+            return Err(format!("image duration {} must be longer than two overlaps of {}", duration, self.overlap));
+        }
+        Ok(durations)
+    }
+
+    fn validate(&self) -> Result<(), String> {
+        if self.width == 0 || self.height == 0 {
+            return Err(format!("frame size {}x{} is empty", self.width, self.height));
+        }
+        for (name, value) in [("overlap", self.overlap), ("lead in", self.lead_in), ("lead out", self.lead_out)] {
+            if !value.is_finite() || value < 0.0 {
+                return Err(format!("invalid {} {}", name, value));
+            }
+        }
+        if self.overlap == 0.0 {
+            return Err(String::from("overlap must be positive"));
+        }
+        self.background.validate()?;
+        self.preset.validate()
+    }
+}
+
+/// Camera motion of an image between its settled start and end framing.
+/// Pans zoom in a little when the image has no room to move along their direction.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum MotionStyle {
+    ZoomIn,
+    ZoomOut,
+    /// The view moves to the left side of the image.
+    PanLeft,
+    PanRight,
+    PanUp,
+    PanDown,
+    /// Slow zoom in with the view moving from the top left to the bottom right of the image.
+    DiagonalDrift,
+    /// The image starts zoomed in and slightly rotated and settles straight.
+    RotateSettle,
+    StaticHold,
+}
+
+impl MotionStyle {
+    pub const ALL: [MotionStyle; 9] = [
+        MotionStyle::ZoomIn, MotionStyle::ZoomOut, MotionStyle::PanLeft, MotionStyle::PanRight, MotionStyle::PanUp,
+        MotionStyle::PanDown, MotionStyle::DiagonalDrift, MotionStyle::RotateSettle, MotionStyle::StaticHold,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            MotionStyle::ZoomIn => "zoom_in",
+            MotionStyle::ZoomOut => "zoom_out",
+            MotionStyle::PanLeft => "pan_left",
+            MotionStyle::PanRight => "pan_right",
+            MotionStyle::PanUp => "pan_up",
+            MotionStyle::PanDown => "pan_down",
+            MotionStyle::DiagonalDrift => "diagonal_drift",
+            MotionStyle::RotateSettle => "rotate_settle",
+            MotionStyle::StaticHold => "static_hold",
+        }
+    }
+
+    /// Style following the image aspect ratio relative to the frame one:
+    /// images wider than the frame pan horizontally, taller ones pan vertically,
+    /// images of about the frame shape zoom. `reverse` swaps the pan direction or zooms out,
+    /// with a focus region it is ignored and the motion ends on the region instead.
+    pub fn for_aspect(image: &RenkiImage, width: usize, height: usize, reverse: bool, preset: &MotionPreset,
+                      focus: Option<&FocusRegion>) -> MotionStyle {
+        let (image_width, image_height) = (image.width as f64, image.height as f64);
+        let cover_scale = (width as f64 / image_width).max(height as f64 / image_height);
+        let style = if Motion::pan_anchors(image_width, width as f64 / cover_scale, preset.pan_margin).is_some() {
+            MotionStyle::PanRight
+        } else if Motion::pan_anchors(image_height, height as f64 / cover_scale, preset.pan_margin).is_some() {
+            MotionStyle::PanDown
+        } else {
+            MotionStyle::ZoomIn
+        };
+        let reverse = match (focus, style) {
+            (Some(focus), MotionStyle::PanRight) => focus.center_x() < image_width * 0.5,
+            (Some(focus), MotionStyle::PanDown) => focus.center_y() < image_height * 0.5,
+            (Some(_), _) => false,
+            (None, _) => reverse,
+        };
+        match (style, reverse) {
+            (MotionStyle::PanRight, true) => MotionStyle::PanLeft,
+            (MotionStyle::PanDown, true) => MotionStyle::PanUp,
+            (MotionStyle::ZoomIn, true) => MotionStyle::ZoomOut,
+            _ => style,
+        }
+    }
+}
+
+impl FromStr for MotionStyle {
+    type Err = String;
+
+    fn from_str(name: &str) -> Result<Self, Self::Err> {
+        MotionStyle::ALL.iter().find(|style| style.name() == name).copied()
+            .ok_or_else(|| format!("unknown motion style {}", name))
+    }
+}
+
+/// Settled framing of an image: the image point shown at the frame center, the image scale and rotation.
+#[derive(Clone, Debug, PartialEq)]
+struct View {
+    anchor_x: f64,
+    anchor_y: f64,
+    scale: f64,
+    angle: f64,
+}
+
+impl View {
+    /// Moves the anchor so the frame shown by the view keeps the focus region, or its center if the region
+    /// does not fit, and stays inside the image. The image rotation is ignored.
+    fn framing(&self, focus: &FocusRegion, image: &RenkiImage, width: usize, height: usize) -> View {
+        let frame_axis = |anchor: f64, image_size: f64, frame_size: f64, focus_start: f64, focus_size: f64| {
+            let window_size = frame_size / self.scale;
+            let half = window_size * 0.5;
+            let anchor = if focus_size <= window_size {
+                anchor.clamp(focus_start + focus_size - half, focus_start + half)
+            } else {
+                focus_start + focus_size * 0.5
+            };
+            if window_size < image_size { anchor.clamp(half, image_size - half) } else { image_size * 0.5 }
+        };
+        View {
+            anchor_x: frame_axis(self.anchor_x, image.width as f64, width as f64, focus.x, focus.width),
+            anchor_y: frame_axis(self.anchor_y, image.height as f64, height as f64, focus.y, focus.height),
+            ..self.clone()
+        }
+    }
+}
+
+/// Motion of an image between its settled start and end views,
+/// `mid_zoom` is the extra zoom at the middle of the motion.
+#[derive(Clone, Debug, PartialEq)]
+struct Motion {
+    start: View,
+    end: View,
+    mid_zoom: f64,
+}
+
+impl Motion {
+    /// Anchors of a pan along one axis of the image, `None` if the image is too short along it to pan.
+    /// `margin` is the part of the window kept between the pan ends and the image border.
+    fn pan_anchors(image_size: f64, window_size: f64, margin: f64) -> Option<(f64, f64)> {
+        let slack = image_size - window_size;
+        let margin = (window_size * margin).min(slack * 0.25);
+        if slack - 2.0 * margin < window_size * 0.05 {
+            None
+        } else {
+            Some((window_size * 0.5 + margin, image_size - window_size * 0.5 - margin))
+        }
+    }
+
+    /// Anchors and scale of a pan along one axis, zooming in if the image is too short along it at `scale`.
+    fn pan_range(image_size: f64, frame_size: f64, scale: f64, margin: f64) -> (f64, f64, f64) {
+        if let Some((start, end)) = Motion::pan_anchors(image_size, frame_size / scale, margin) {
+            return (start, end, scale);
+        }
+        let scale = scale * 1.2;
+        let (start, end) = Motion::pan_anchors(image_size, frame_size / scale, margin).unwrap_or((image_size * 0.5, image_size * 0.5));
+        (start, end, scale)
+    }
+
+    fn from_style(style: MotionStyle, image: &RenkiImage, width: usize, height: usize, preset: &MotionPreset,
+                  focus: Option<&FocusRegion>) -> Motion {
+        let (image_width, image_height) = (image.width as f64, image.height as f64);
+        let (frame_width, frame_height) = (width as f64, height as f64);
+        let cover_scale = (frame_width / image_width).max(frame_height / image_height);
+        let center = View { anchor_x: image_width * 0.5, anchor_y: image_height * 0.5, scale: cover_scale, angle: 0.0 };
+        let reversed = |motion: Motion| Motion { start: motion.end, end: motion.start, mid_zoom: motion.mid_zoom };
+
+        match style {
+            MotionStyle::ZoomIn => {
+                let (anchor_x, anchor_y) = focus.map_or((center.anchor_x, center.anchor_y), |focus| (focus.center_x(), focus.center_y()));
+                Motion {
+                    start: center.clone(),
+                    end: View { anchor_x, anchor_y, scale: cover_scale * preset.zoom, angle: 0.0 },
+                    mid_zoom: 1.0,
+                }
+            }
+            MotionStyle::ZoomOut => reversed(Motion::from_style(MotionStyle::ZoomIn, image, width, height, preset, focus)),
+            MotionStyle::PanRight => {
+                let (left, right, scale) = Motion::pan_range(image_width, frame_width, cover_scale, preset.pan_margin);
+                Motion {
+                    start: View { anchor_x: left, scale, ..center.clone() },
+                    end: View { anchor_x: right, scale, ..center },
+                    mid_zoom: preset.pan_zoom,
+                }
+            }
+            MotionStyle::PanLeft => reversed(Motion::from_style(MotionStyle::PanRight, image, width, height, preset, focus)),
+            MotionStyle::PanDown => {
+                let (top, bottom, scale) = Motion::pan_range(image_height, frame_height, cover_scale, preset.pan_margin);
+                Motion {
+                    start: View { anchor_y: top, scale, ..center.clone() },
+                    end: View { anchor_y: bottom, scale, ..center },
+                    mid_zoom: preset.pan_zoom,
+                }
+            }
+            MotionStyle::PanUp => reversed(Motion::from_style(MotionStyle::PanDown, image, width, height, preset, focus)),
+            MotionStyle::DiagonalDrift => {
+                let scale = cover_scale * preset.drift_zoom;
+                let (left, right) = Motion::pan_anchors(image_width, frame_width / scale, preset.pan_margin).unwrap_or((center.anchor_x, center.anchor_x));
+                let (top, bottom) = Motion::pan_anchors(image_height, frame_height / scale, preset.pan_margin).unwrap_or((center.anchor_y, center.anchor_y));
+                Motion {
+                    start: View { anchor_x: left, anchor_y: top, scale, angle: 0.0 },
+                    end: View { anchor_x: right, anchor_y: bottom, scale: scale * preset.zoom, angle: 0.0 },
+                    mid_zoom: 1.0,
+                }
+            }
+            MotionStyle::RotateSettle => Motion {
+                start: View { scale: cover_scale * preset.drift_zoom, angle: preset.settle_angle, ..center.clone() },
+                end: center,
+                mid_zoom: 1.0,
+            },
+            MotionStyle::StaticHold => Motion { start: center.clone(), end: center, mid_zoom: 1.0 },
+        }
+    }
+
+    fn mid(&self) -> View {
+        View {
+            anchor_x: (self.start.anchor_x + self.end.anchor_x) * 0.5,
+            anchor_y: (self.start.anchor_y + self.end.anchor_y) * 0.5,
+            scale: (self.start.scale * self.end.scale).sqrt() * self.mid_zoom,
+            angle: (self.start.angle + self.end.angle) * 0.5,
+        }
+    }
+}
+
+/// Minimum scale at which the image transformed by `point` covers the whole frame,
+/// `None` if no scale does because the anchor is on the image border.
+pub(crate) fn min_cover_scale(image: &RenkiImage, width: usize, height: usize, point: &ScenarioPoint) -> Option<f64> {
+    let (image_width, image_height) = (image.width as f64, image.height as f64);
+    if point.anchor_x <= 0.0 || point.anchor_x >= image_width || point.anchor_y <= 0.0 || point.anchor_y >= image_height {
+        return None;
+    }
+    // at unit scale the inverse transform gives the frame corners relative to the anchor in image pixels,
+    // a scale s brings them closer to the anchor by s
+    let inverse = ScenarioPoint { scale: 1.0, ..point.clone() }.matrix().invert()?;
+    let corners = [(0.0, 0.0), (width as f64, 0.0), (width as f64, height as f64), (0.0, height as f64)];
+    let mut scale = 0_f64;
+    for (x, y) in corners {
+        let corner = inverse.apply(&Point::new(x, y));
+        let (dx, dy) = (corner.x - point.anchor_x, corner.y - point.anchor_y);
+        scale = scale.max(if dx < 0.0 { -dx / point.anchor_x } else { dx / (image_width - point.anchor_x) });
+        scale = scale.max(if dy < 0.0 { -dy / point.anchor_y } else { dy / (image_height - point.anchor_y) });
+    }
+    Some(scale)
+}
+
+impl Scenario {
+    pub fn generate_scenario(images: &[String], images_map: &HashMap<String, RenkiImage>,
+                             options: &GeneratorOptions) -> Result<Scenario, String> {
+        options.validate()?;
+        if images.is_empty() {
+            return Err(String::from("no images to generate scenario"));
+        }
+        let mut loaded_images = Vec::with_capacity(images.len());
+        for image_filename in images {
+            let image = images_map.get(image_filename).ok_or_else(|| format!("Image {} is not loaded", image_filename))?;
+            if let Some(focus) = options.focus.get(image_filename) {
+                focus.validate(image.width as f64, image.height as f64).map_err(|e| format!("image {}: {}", image_filename, e))?;
+            }
+            loaded_images.push(image);
+        }
+        let durations = options.image_durations(&loaded_images)?;
+        let (width, height) = (options.width, options.height);
+        let preset = &options.preset;
+
+        let mut images_scenarios = Vec::with_capacity(images.len());
+        let mut start_time = 0.0;
+        let mut end_time = 0.0;
+        for image_index in 0..images.len() {
+            let image_filename = &images[image_index];
+            let image = loaded_images[image_index];
+            let focus = match options.focus.get(image_filename) {
+                Some(focus) => Some(focus.clone()),
+                None if options.saliency => salient_region(image),
+                None => None,
+            };
+            let focus = focus.as_ref();
+            let style = match (options.styles.get(image_filename), options.seed) {
+                (Some(style), _) => *style,
+                (None, Some(seed)) => MotionStyle::ALL[Random::for_stream(seed, image_index as u64).below(MotionStyle::ALL.len())],
+                (None, None) => MotionStyle::for_aspect(image, width, height, image_index % 2 == 1, preset, focus),
+            };
+            let motion = Motion::from_style(style, image, width, height, preset, focus);
+            let [start, mid, end] = match focus {
+                Some(focus) => [&motion.start, &motion.mid(), &motion.end].map(|view| view.framing(focus, image, width, height)),
+                None => [motion.start.clone(), motion.mid(), motion.end.clone()],
+            };
+            let offset_x = width as f64 * 0.5;
+            let offset_y = height as f64 * 0.5;
+
+            let image_duration = durations[image_index];
+            let overlap = options.overlap;
+
+            let mut points = vec![
+                ScenarioPoint {
+                    time: start_time,
+                    anchor_x: start.anchor_x, anchor_y: start.anchor_y, offset_x, offset_y,
+                    angle: preset.entry_angle, scale: start.scale * preset.entry_scale, alpha: 0.0, tangents: None, easing: Easing::Linear},
+                ScenarioPoint {
+                    time: start_time + overlap,
+                    anchor_x: start.anchor_x, anchor_y: start.anchor_y, offset_x, offset_y,
+                    angle: start.angle, scale: start.scale, alpha: preset.settle_alpha, tangents: None, easing: Easing::Linear},
+                ScenarioPoint {
+                    time: start_time + image_duration * 0.5,
+                    anchor_x: mid.anchor_x, anchor_y: mid.anchor_y, offset_x, offset_y,
+                    angle: mid.angle, scale: mid.scale, alpha: 1.0, tangents: None, easing: Easing::Linear},
+                ScenarioPoint {
+                    time: start_time + image_duration - overlap,
+                    anchor_x: end.anchor_x, anchor_y: end.anchor_y, offset_x, offset_y,
+                    angle: end.angle, scale: end.scale, alpha: preset.settle_alpha, tangents: None, easing: Easing::Linear},
+                ScenarioPoint {
+                    time: start_time + image_duration,
+                    anchor_x: end.anchor_x, anchor_y: end.anchor_y, offset_x, offset_y,
+                    angle: preset.exit_angle, scale: end.scale * preset.entry_scale, alpha: 0.0, tangents: None, easing: Easing::Linear},
+            ];
+
+            // transitions show both images fully opaque, the settle fade would jump at their edges
+            if options.transition.is_some() {
+                if image_index > 0 {
+                    points[1].alpha = 1.0;
+                }
+                if image_index < images.len() - 1 {
+                    points[3].alpha = 1.0;
+                }
+            }
+            // the holds are keyed separately from the image duration, so `Scenario::image_durations` can leave them out
+            if image_index == 0 && options.lead_in > 0.0 {
+                for point in points.iter_mut() {
+                    point.time += options.lead_in;
+                }
+                let settled = ScenarioPoint { alpha: 1.0, ..points[1].clone() };
+                points[1] = settled.clone();
+                points[0] = ScenarioPoint { time: points[0].time, ..settled.clone() };
+                points.insert(0, ScenarioPoint { time: start_time, ..settled });
+            }
+            if image_index == images.len() - 1 && options.lead_out > 0.0 {
+                let last = points.len() - 1;
+                let settled = ScenarioPoint { alpha: 1.0, ..points[last - 1].clone() };
+                points[last - 1] = settled.clone();
+                points[last] = ScenarioPoint { time: points[last].time, ..settled.clone() };
+                points.push(ScenarioPoint { time: points[last].time + options.lead_out, ..settled });
+            }
+            if options.cover_frame {
+                for point in points.iter_mut() {
+                    if let Some(scale) = min_cover_scale(image, width, height, point) {
+                        point.scale = point.scale.max(scale);
+                    }
+                }
+            }
+            end_time = points[points.len() - 1].time;
+
+            let image_scenario = ImageScenario::from_points(image_filename, &points, &InterpolationSettings::default())?;
+            let transition = if image_index > 0 { options.transition.clone() } else { None };
+            images_scenarios.push(ImageScenario { focus: focus.cloned(), transition, ..image_scenario });
+            start_time = end_time - overlap;
+        }
+        Ok(Scenario {
+            version: SCENARIO_VERSION, width, height, fps: options.fps, duration: end_time, images: images_scenarios,
+            background: options.background.clone(),
+        })
+    }
+}
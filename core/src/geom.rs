@@ -55,18 +55,20 @@ impl Geom {
         }
     }
 
+    /// Whether `p` is inside the clip edge. Points on the edge are inside: a vertex lying exactly on the pixel
+    /// border, as with pixel aligned transforms, would otherwise be dropped together with the area it bounds.
     fn is_right_side(clip_index: usize, p: &Point) -> bool {
         match clip_index {
-            0 => p.y > 0.0,
-            1 => p.x > 0.0,
-            2 => p.y < 1.0,
-            3 => p.x < 1.0,
+            0 => p.y >= 0.0,
+            1 => p.x >= 0.0,
+            2 => p.y <= 1.0,
+            3 => p.x <= 1.0,
             _ => false
         }
     }
 
-    pub fn clip(subject_polygon: &Vec<Point>) -> Vec<Point> {
-        let mut result_ring = subject_polygon.clone();
+    pub fn clip(subject_polygon: &[Point]) -> Vec<Point> {
+        let mut result_ring = subject_polygon.to_vec();
         for clip_index in 0..4 {
             let input = result_ring;
             //println!("Clip index {}: cur result {:?}", clip_index, &input);
@@ -74,16 +76,12 @@ impl Geom {
             result_ring = vec![];
             for p1 in input.iter() {
                 let intersection = Geom::intersection_with_clipping(clip_index, p0, p1);
-                if intersection.is_some() {
+                if Geom::is_right_side(clip_index, p0) {
+                    result_ring.push(p0.clone());
+                }
+                if let Some(intersection) = intersection {
                     //println!("Found intersection {:?}", &intersection);
-                    if Geom::is_right_side(clip_index, &p0) {
-                        result_ring.push(p0.clone());
-                    }
-                    result_ring.push(intersection.unwrap());
-                } else {
-                    if Geom::is_right_side(clip_index, &p0) {
-                        result_ring.push(p0.clone());
-                    }
+                    result_ring.push(intersection);
                 }
                 p0 = p1;
             }
@@ -92,7 +90,7 @@ impl Geom {
         result_ring
     }
 
-    pub fn polygon_area(polygon: &Vec<Point>) -> f64 {
+    pub fn polygon_area(polygon: &[Point]) -> f64 {
         let mut area = 0.0;
         for i in 0..polygon.len() - 2 {
             let p0 = &polygon[0];
@@ -0,0 +1,143 @@
+use serde::{Deserialize, Serialize};
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Interpolation {
+    Linear,
+    CatmullRom,
+    #[default]
+    MonotoneCubic,
+    Bezier,
+}
+
+/// Easing of the segment that starts at a keyframe, remaps the segment progress before interpolation.
+/// `ease_in`, `ease_out` and `ease_in_out` are the CSS timing curves, `step` holds the value until the next keyframe.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Easing {
+    #[default]
+    Linear,
+    EaseIn,
+    EaseOut,
+    EaseInOut,
+    CubicBezier { x1: f64, y1: f64, x2: f64, y2: f64 },
+    Step,
+}
+
+/// Tangent of a property at a keyframe, in value units per unit of time.
+/// Only used by [`Interpolation::Bezier`], the Bezier handles are placed at a third of the segment.
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Tangent {
+    pub incoming: f64,
+    pub outgoing: f64,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Keyframe {
+    pub time: f64,
+    pub value: f64,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tangent: Option<Tangent>,
+    #[serde(default, skip_serializing_if = "Easing::is_linear")]
+    pub easing: Easing,
+}
+
+impl Easing {
+    pub fn is_linear(&self) -> bool {
+        *self == Easing::Linear
+    }
+
+    fn cubic_bezier(x1: f64, y1: f64, x2: f64, y2: f64, x: f64) -> f64 {
+        let bezier = |p1: f64, p2: f64, t: f64| 3.0 * (1.0 - t) * (1.0 - t) * t * p1 + 3.0 * (1.0 - t) * t * t * p2 + t * t * t;
+        // x(t) is monotone for control points inside 0..1, so bisection always converges
+        let (x1, x2) = (x1.clamp(0.0, 1.0), x2.clamp(0.0, 1.0));
+        let (mut low, mut high) = (0.0, 1.0);
+        let mut t = x;
+        for _ in 0..64 {
+            let value = bezier(x1, x2, t);
+            if (value - x).abs() < 1e-12 {
+                break;
+            }
+            if value < x { low = t; } else { high = t; }
+            t = (low + high) * 0.5;
+        }
+        bezier(y1, y2, t)
+    }
+
+    pub fn apply(&self, progress: f64) -> f64 {
+        match *self {
+            Easing::Linear => progress,
+            Easing::EaseIn => Easing::cubic_bezier(0.42, 0.0, 1.0, 1.0, progress),
+            Easing::EaseOut => Easing::cubic_bezier(0.0, 0.0, 0.58, 1.0, progress),
+            Easing::EaseInOut => Easing::cubic_bezier(0.42, 0.0, 0.58, 1.0, progress),
+            Easing::CubicBezier { x1, y1, x2, y2 } => Easing::cubic_bezier(x1, y1, x2, y2, progress),
+            Easing::Step => if progress < 1.0 { 0.0 } else { 1.0 },
+        }
+    }
+}
+
+impl Interpolation {
+    fn catmull_rom_slope(keys: &[Keyframe], index: usize) -> f64 {
+        let prev = &keys[if index > 0 { index - 1 } else { index }];
+        let next = &keys[if index + 1 < keys.len() { index + 1 } else { index }];
+        (next.value - prev.value) / (next.time - prev.time)
+    }
+
+    fn monotone_slope(keys: &[Keyframe], index: usize) -> f64 {
+        let secant = |i: usize| (keys[i + 1].value - keys[i].value) / (keys[i + 1].time - keys[i].time);
+        if index == 0 {
+            return secant(0);
+        }
+        if index == keys.len() - 1 {
+            return secant(index - 1);
+        }
+        let (d0, d1) = (secant(index - 1), secant(index));
+        if d0 * d1 <= 0.0 {
+            return 0.0;
+        }
+        // Fritsch-Butland weighted harmonic mean keeps every segment monotone
+        let h0 = keys[index].time - keys[index - 1].time;
+        let h1 = keys[index + 1].time - keys[index].time;
+        3.0 * (h0 + h1) / ((2.0 * h1 + h0) / d0 + (h1 + 2.0 * h0) / d1)
+    }
+
+    fn slopes(&self, keys: &[Keyframe], index: usize) -> (f64, f64) {
+        match self {
+            Interpolation::Linear => (0.0, 0.0),
+            Interpolation::CatmullRom => (Interpolation::catmull_rom_slope(keys, index), Interpolation::catmull_rom_slope(keys, index + 1)),
+            Interpolation::MonotoneCubic => (Interpolation::monotone_slope(keys, index), Interpolation::monotone_slope(keys, index + 1)),
+            Interpolation::Bezier => {
+                let outgoing = keys[index].tangent.map(|t| t.outgoing)
+                    .unwrap_or_else(|| Interpolation::catmull_rom_slope(keys, index));
+                let incoming = keys[index + 1].tangent.map(|t| t.incoming)
+                    .unwrap_or_else(|| Interpolation::catmull_rom_slope(keys, index + 1));
+                (outgoing, incoming)
+            }
+        }
+    }
+
+    /// Evaluates the curve through `keys` at `time`, keys must be sorted by time.
+    /// Outside of the keys range the first or last value is held.
+    pub fn interpolate(&self, keys: &[Keyframe], time: f64) -> f64 {
+        if time <= keys[0].time {
+            return keys[0].value;
+        }
+        if time >= keys[keys.len() - 1].time {
+            return keys[keys.len() - 1].value;
+        }
+        let index = keys.windows(2).position(|pair| time < pair[1].time).unwrap();
+        let (k0, k1) = (&keys[index], &keys[index + 1]);
+        let h = k1.time - k0.time;
+        let u = k0.easing.apply((time - k0.time) / h);
+        if *self == Interpolation::Linear {
+            return k0.value + (k1.value - k0.value) * u;
+        }
+        let (m0, m1) = self.slopes(keys, index);
+        let u2 = u * u;
+        let u3 = u2 * u;
+        (2.0 * u3 - 3.0 * u2 + 1.0) * k0.value
+            + (u3 - 2.0 * u2 + u) * h * m0
+            + (-2.0 * u3 + 3.0 * u2) * k1.value
+            + (u3 - u2) * h * m1
+    }
+}
@@ -1,19 +1,220 @@
+use std::collections::hash_map::Entry;
 use std::collections::HashMap;
+use std::ffi::OsString;
+use std::path::{Component, Path, PathBuf};
 use crate::renki_image::RenkiImage;
-use crate::scenario::Scenario;
 
 mod renki_image;
 mod matrix;
 mod geom;
 mod scenario;
+mod interpolation;
+mod track;
+mod frame_rate;
+mod generator;
+mod random;
+mod preset;
+mod focus;
+mod saliency;
+mod transition;
+mod background;
+mod resample;
+mod render;
+mod color;
+mod dither;
+mod output;
+
+pub use crate::scenario::{Scenario, ImageScenario, ImageTracks, ScenarioPoint, ScenarioTangents, InterpolationSettings, SCENARIO_VERSION};
+pub use crate::interpolation::{Easing, Interpolation, Keyframe, Tangent};
+pub use crate::track::{Track, ValueSpace};
+pub use crate::frame_rate::FrameRate;
+pub use crate::generator::{GeneratorOptions, ImageDurations, MotionStyle};
+pub use crate::preset::MotionPreset;
+pub use crate::focus::FocusRegion;
+pub use crate::saliency::salient_region;
+pub use crate::transition::{Direction, Transition, TransitionKind};
+pub use crate::background::Background;
+pub use crate::resample::Resampler;
+pub use crate::render::RenderOptions;
+pub use crate::dither::Dither;
+pub use crate::output::OutputFormat;
+
+pub struct RenkiCore {}
+
+impl RenkiCore {
+    /// Converts the loaded sRGB images to the color space the frames are composited in and builds their mipmaps.
+    fn decode_images(images_map: HashMap<String, RenkiImage>, render_options: &RenderOptions) -> HashMap<String, RenkiImage> {
+        images_map.into_iter()
+            .map(|(filename, image)| {
+                let mut image = if render_options.linear { image.to_linear() } else { image };
+                if render_options.mipmaps {
+                    image.build_mipmaps();
+                }
+                (filename, image)
+            })
+            .collect()
+    }
+
+    fn load_images(files: &[String]) -> Result<HashMap<String, RenkiImage>, String> {
+        let mut images_map = HashMap::new();
+        for filename in files {
+            let image = RenkiImage::from_img(filename)?;
+            images_map.insert(filename.clone(), image);
+        }
+        Ok(images_map)
+    }
+
+    /// Image files in the `dir` directory sorted by path, other files like the focus sidecars are skipped.
+    pub fn scan_images(dir: &str) -> Result<Vec<String>, String> {
+        let entries = std::fs::read_dir(dir).map_err(|e| format!("Failed to scan directory {}: {}", dir, e))?;
+        let mut files = Vec::new();
+        for entry in entries.flatten() {
+            let path = entry.path();
+            if path.is_file() && image::ImageFormat::from_path(&path).is_ok() {
+                files.push(path.to_string_lossy().into_owned());
+            }
+        }
+        files.sort();
+        Ok(files)
+    }
+
+    /// Loads the images of a scenario, their paths are relative to the `base_dir` of the scenario file.
+    fn load_scenario_images(scenario: &Scenario, base_dir: &Path) -> Result<HashMap<String, RenkiImage>, String> {
+        let mut images_map = HashMap::new();
+        for filename in scenario.image_files() {
+            let image_path = base_dir.join(&filename);
+            let image = RenkiImage::from_img(&image_path.to_string_lossy())?;
+            images_map.insert(filename, image);
+        }
+        Ok(images_map)
+    }
+
+    /// Components of the absolute `path` with `.` and `..` resolved, equal for any two spellings of a path.
+    fn path_components(path: &Path) -> Result<Vec<OsString>, String> {
+        let absolute = std::path::absolute(path).map_err(|e| format!("Failed to resolve path {}: {}", path.display(), e))?;
+        let mut components = Vec::new();
+        for component in absolute.components() {
+            match component {
+                Component::CurDir => {}
+                Component::ParentDir => {
+                    components.pop();
+                }
+                component => components.push(component.as_os_str().to_os_string()),
+            }
+        }
+        Ok(components)
+    }
+
+    /// `path` relative to the `base_dir` directory, both are taken relative to the working directory.
+    fn relative_path(path: &str, base_dir: &Path) -> Result<String, String> {
+        let (path, base_dir) = (RenkiCore::path_components(Path::new(path))?, RenkiCore::path_components(base_dir)?);
+        let common = path.iter().zip(base_dir.iter()).take_while(|(first, second)| first == second).count();
+        let mut relative = PathBuf::new();
+        for _component in &base_dir[common..] {
+            relative.push("..");
+        }
+        relative.extend(&path[common..]);
+        Ok(relative.to_string_lossy().into_owned())
+    }
+
+    /// Preset by name, custom presets from the `presets_path` file take precedence over the built in ones.
+    pub fn preset(name: &str, presets_path: Option<&str>) -> Result<MotionPreset, String> {
+        if let Some(presets_path) = presets_path {
+            if let Some(preset) = MotionPreset::load_presets(presets_path)?.remove(name) {
+                return Ok(preset);
+            }
+        }
+        MotionPreset::named(name).ok_or_else(|| format!("unknown preset {}", name))
+    }
+
+    /// Focus regions of the images of a scenario file, keyed by the image paths resolved against the scenario directory.
+    pub fn focus_regions(scenario_path: &str) -> Result<HashMap<String, FocusRegion>, String> {
+        let scenario = Scenario::load(scenario_path)?;
+        let base_dir = Path::new(scenario_path).parent().unwrap_or_else(|| Path::new(""));
+        Ok(scenario.focus_regions().into_iter()
+            .map(|(image, focus)| (base_dir.join(image).to_string_lossy().into_owned(), focus))
+            .collect())
+    }
+
+    /// Generates the scenario of the images, focus regions missing from the options are read from the image sidecar files.
+    /// The focus regions of the options may name an image by any path leading to it.
+    fn generate(files: &[String], options: &GeneratorOptions) -> Result<(HashMap<String, RenkiImage>, Scenario), String> {
+        let mut images_map = RenkiCore::load_images(files)?;
+        let mut options = options.clone();
+        let mut focus_regions: HashMap<Vec<OsString>, FocusRegion> = HashMap::new();
+        for (path, focus) in std::mem::take(&mut options.focus) {
+            focus_regions.insert(RenkiCore::path_components(Path::new(&path))?, focus);
+        }
+        for filename in files {
+            let focus = match focus_regions.remove(&RenkiCore::path_components(Path::new(filename))?) {
+                Some(focus) => Some(focus),
+                None => FocusRegion::load_sidecar(filename)?,
+            };
+            if let Some(focus) = focus {
+                options.focus.insert(filename.clone(), focus);
+            }
+        }
+        let scenario = Scenario::generate_scenario(files, &images_map, &options)?;
+        // transition mattes and the background image are loaded with the images
+        for filename in scenario.image_files() {
+            if let Entry::Vacant(entry) = images_map.entry(filename) {
+                let image = RenkiImage::from_img(entry.key())?;
+                entry.insert(image);
+            }
+        }
+        Ok((images_map, scenario))
+    }
+
+    pub fn render_images(files: &[String], options: &GeneratorOptions, render_options: &RenderOptions) -> Result<(), String> {
+        let (images_map, scenario) = RenkiCore::generate(files, options)?;
+        let images_map = RenkiCore::decode_images(images_map, render_options);
+        scenario.render(&images_map, "frames/", render_options)
+    }
+
+    pub fn export_scenario(files: &[String], options: &GeneratorOptions, scenario_path: &str) -> Result<(), String> {
+        let (_, mut scenario) = RenkiCore::generate(files, options)?;
+        // the renderer resolves image paths relative to the scenario file
+        let base_dir = Path::new(scenario_path).parent().unwrap_or_else(|| Path::new(""));
+        let relative_paths: HashMap<String, String> = scenario.image_files().into_iter()
+            .map(|file| RenkiCore::relative_path(&file, base_dir).map(|relative| (file, relative)))
+            .collect::<Result<_, _>>()?;
+        scenario.map_image_files(|file| relative_paths[file].clone());
+        scenario.save(scenario_path)
+    }
+
+    pub fn render_scenario(scenario_path: &str, render_options: &RenderOptions) -> Result<(), String> {
+        let scenario = Scenario::load(scenario_path)?;
+        let base_dir = Path::new(scenario_path).parent().unwrap_or_else(|| Path::new(""));
+        let images_map = RenkiCore::load_scenario_images(&scenario, base_dir)?;
+        let images_map = RenkiCore::decode_images(images_map, render_options);
+        scenario.render(&images_map, "frames/", render_options)
+    }
+}
 
 #[cfg(test)]
 mod tests {
     use crate::geom::{Geom, Point};
     use crate::renki_image::RenkiImage;
     use crate::matrix::Matrix2d;
-    use crate::scenario::Scenario;
+    use crate::scenario::{ImageScenario, ImageTracks, InterpolationSettings, Scenario, ScenarioPoint, SCENARIO_VERSION};
+    use crate::track::{Track, ValueSpace};
+    use crate::frame_rate::FrameRate;
+    use crate::generator::{min_cover_scale, GeneratorOptions, ImageDurations, MotionStyle};
+    use crate::preset::MotionPreset;
+    use crate::focus::FocusRegion;
+    use crate::saliency::salient_region;
+    use crate::transition::{Direction, Transition, TransitionKind};
+    use crate::background::Background;
+    use crate::resample::Resampler;
+    use crate::render::RenderOptions;
+    use crate::color;
+    use crate::dither::Dither;
+    use crate::output::OutputFormat;
+    use crate::interpolation::{Easing, Interpolation, Keyframe, Tangent};
+    use crate::RenkiCore;
     use std::collections::HashMap;
+    use std::path::PathBuf;
+    use std::sync::atomic::{AtomicUsize, Ordering};
 
     #[test]
     fn algorithm_test() {
@@ -30,9 +231,18 @@ mod tests {
         println!("Result 2 area {}", result_area);
     }
 
+    #[test]
+    fn test_clip_pixel_border() {
+        let subject_polygon = vec![Point {x: 0.5, y: 0.5}, Point {x: 0.5, y: 1.5}, Point {x: 1.5, y: 1.5}];
+        let result = Geom::clip(&subject_polygon);
+        assert!((Geom::polygon_area(&result) - 0.125).abs() < 1e-12);
+        let subject_polygon = vec![Point {x: 0.0, y: 0.0}, Point {x: 0.0, y: 1.0}, Point {x: 1.0, y: 1.0}, Point {x: 1.0, y: 0.0}];
+        assert!((Geom::polygon_area(&Geom::clip(&subject_polygon)) - 1.0).abs() < 1e-12);
+    }
+
     #[test]
     fn test_image_transform() {
-        let image = RenkiImage::from_img(&String::from("sample0.jpg")).expect("Failed to load file");
+        let image = RenkiImage::from_img("sample0.jpg").expect("Failed to load file");
         let matrix = Matrix2d::translate(128.0, 72.0)
             .multiply(&Matrix2d::scale(1.25))
             .multiply(&Matrix2d::rotation(0.125))
@@ -44,25 +254,1062 @@ mod tests {
     #[test]
     fn test_scenario_render() {
         let files = vec![String::from("sample0.jpg"), String::from("sample1.jpg")];
+        let images_map = RenkiCore::load_images(&files).expect("Failed to load images");
+        let options = GeneratorOptions { width: 144, height: 144, durations: ImageDurations::Total(4.0), ..GeneratorOptions::default() };
+        let scenario = Scenario::generate_scenario(&files, &images_map, &options).expect("Failed to generate scenario");
+        scenario.validate().expect("Generated scenario is invalid");
+        assert!((scenario.duration - 4.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_scenario_load() {
+        let json = r#"{
+            "width": 144, "height": 144, "length": 10,
+            "images": [{
+                "image": "sample0.jpg",
+                "points": [
+                    {"time": 0.0, "anchor_x": 100.0, "anchor_y": 50.0, "offset_x": 72.0, "offset_y": 72.0, "scale": 1.0, "angle": 0.0, "alpha": 0.0,
+                     "easing": {"cubic_bezier": {"x1": 0.25, "y1": 0.1, "x2": 0.25, "y2": 1.0}}},
+                    {"time": 1.0, "anchor_x": 150.0, "anchor_y": 50.0, "offset_x": 72.0, "offset_y": 72.0, "scale": 1.5, "angle": 0.1, "alpha": 1.0}
+                ]
+            }]
+        }"#;
+        let scenario = Scenario::from_json(json).expect("Failed to parse scenario");
+        assert_eq!(scenario.image_files(), vec![String::from("sample0.jpg")]);
+        assert_eq!(scenario.images[0].tracks.scale.keys[1].value, 1.5);
+        assert_eq!(scenario.frame_count(), 10);
+        assert!((scenario.images[0].tracks.scale.keys[1].time - 0.4).abs() < 1e-12);
+        assert_eq!(scenario.images[0].tracks.scale.keys[0].easing, Easing::CubicBezier { x1: 0.25, y1: 0.1, x2: 0.25, y2: 1.0 });
+
+        let reversed = json.replace("\"time\": 1.0", "\"time\": -1.0");
+        assert!(Scenario::from_json(&reversed).is_err());
+        let no_scale = json.replace("\"scale\": 1.5", "\"scale\": 0.0");
+        assert!(Scenario::from_json(&no_scale).is_err());
+        let future_version = json.replacen("{", "{\"version\": 99,", 1);
+        assert!(Scenario::from_json(&future_version).is_err());
+    }
+
+    #[test]
+    fn test_scenario_export_round_trip() {
+        let files = vec![String::from("sample0.jpg"), String::from("sample1.jpg")];
+        let images_map = RenkiCore::load_images(&files).expect("Failed to load images");
+        let options = GeneratorOptions { width: 1920, height: 1080, fps: FrameRate::from_fps(29.97).unwrap(), ..GeneratorOptions::default() };
+        let scenario = Scenario::generate_scenario(&files, &images_map, &options).expect("Failed to generate scenario");
+        let json = scenario.to_json().expect("Failed to serialize scenario");
+        assert!(json.contains("\"version\": 3"));
+        let loaded = Scenario::from_json(&json).expect("Failed to parse exported scenario");
+        assert_eq!(loaded, scenario);
+    }
+
+    #[test]
+    fn test_interpolation_modes() {
+        let keys: Vec<Keyframe> = [(0.0, 1.5), (0.2, 1.0), (0.5, 1.125), (0.8, 1.0), (1.0, 1.5)].iter()
+            .map(|&(time, value)| Keyframe { time, value, tangent: None, easing: Easing::Linear })
+            .collect();
+        for interpolation in [Interpolation::Linear, Interpolation::CatmullRom, Interpolation::MonotoneCubic, Interpolation::Bezier] {
+            for key in &keys {
+                assert!((interpolation.interpolate(&keys, key.time) - key.value).abs() < 1e-12);
+            }
+        }
+        assert!((Interpolation::Linear.interpolate(&keys, 0.1) - 1.25).abs() < 1e-12);
+        for step in 0..=1000 {
+            let time = step as f64 / 1000.0;
+            let value = Interpolation::MonotoneCubic.interpolate(&keys, time);
+            assert!((1.0..=1.5).contains(&value), "monotone cubic overshoots at {}: {}", time, value);
+        }
+
+        let flat = vec![
+            Keyframe { time: 0.0, value: 0.0, tangent: Some(Tangent { incoming: 0.0, outgoing: 0.0 }), easing: Easing::Linear },
+            Keyframe { time: 1.0, value: 1.0, tangent: Some(Tangent { incoming: 0.0, outgoing: 0.0 }), easing: Easing::Linear },
+        ];
+        assert!((Interpolation::Bezier.interpolate(&flat, 0.25) - 0.15625).abs() < 1e-12);
+    }
+
+    #[test]
+    fn test_easing() {
+        let easings = [Easing::Linear, Easing::EaseIn, Easing::EaseOut, Easing::EaseInOut,
+            Easing::CubicBezier { x1: 0.25, y1: 0.1, x2: 0.25, y2: 1.0 }];
+        for easing in easings {
+            assert!(easing.apply(0.0).abs() < 1e-9);
+            assert!((easing.apply(1.0) - 1.0).abs() < 1e-9);
+        }
+        assert!(Easing::EaseIn.apply(0.25) < 0.25);
+        assert!(Easing::EaseOut.apply(0.25) > 0.25);
+        assert!((Easing::EaseInOut.apply(0.5) - 0.5).abs() < 1e-9);
+
+        let keys = vec![
+            Keyframe { time: 0.0, value: 2.0, tangent: None, easing: Easing::Step },
+            Keyframe { time: 1.0, value: 4.0, tangent: None, easing: Easing::Linear },
+        ];
+        assert_eq!(Interpolation::Linear.interpolate(&keys, 0.99), 2.0);
+        assert_eq!(Interpolation::Linear.interpolate(&keys, 1.0), 4.0);
+    }
+
+    #[test]
+    fn test_independent_tracks() {
+        let track = |keys: &[(f64, f64)]| Track::new(Interpolation::Linear, keys.iter()
+            .map(|&(time, value)| Keyframe { time, value, tangent: None, easing: Easing::Linear })
+            .collect());
+        let tracks = ImageTracks {
+            anchor_x: track(&[(0.0, 100.0)]),
+            anchor_y: track(&[(0.0, 50.0)]),
+            offset_x: track(&[(0.0, 72.0)]),
+            offset_y: track(&[(0.0, 72.0)]),
+            scale: track(&[(0.0, 1.0), (1.0, 2.0)]),
+            angle: Track::constant(0.0),
+            alpha: track(&[(0.2, 0.0), (0.4, 1.0), (0.9, 0.0)]),
+        };
+        assert_eq!(tracks.start_time(), 0.0);
+        assert_eq!(tracks.end_time(), 1.0);
+        assert_eq!(tracks.alpha.sample(0.1), 0.0);
+        assert!((tracks.alpha.sample(0.3) - 0.5).abs() < 1e-12);
+        assert!((tracks.scale.sample(0.3) - 1.3).abs() < 1e-12);
+        assert_eq!(tracks.anchor_x.sample(0.3), 100.0);
+
+        let image_scenario = ImageScenario { image: String::from("sample0.jpg"), tracks, focus: None, transition: None };
+        let scenario = Scenario { version: crate::scenario::SCENARIO_VERSION, width: 144, height: 144,
+            fps: FrameRate::new(24, 1), duration: 1.0, images: vec![image_scenario], background: Background::default() };
+        scenario.validate().expect("Scenario with independent tracks is invalid");
+        let loaded = Scenario::from_json(&scenario.to_json().unwrap()).expect("Failed to parse scenario");
+        assert_eq!(loaded, scenario);
+    }
+
+    #[test]
+    fn test_default_tracks() {
+        let track = |keys: &[(f64, f64)]| Track::new(Interpolation::Linear, keys.iter()
+            .map(|&(time, value)| Keyframe { time, value, tangent: None, easing: Easing::Linear })
+            .collect());
+        let tracks = ImageTracks {
+            anchor_x: track(&[(5.0, 100.0), (7.0, 120.0)]),
+            anchor_y: track(&[(5.0, 50.0)]),
+            offset_x: track(&[(5.0, 72.0)]),
+            offset_y: track(&[(5.0, 72.0)]),
+            scale: track(&[(5.0, 1.0), (8.0, 2.0)]),
+            angle: Track::constant(0.0),
+            alpha: Track::constant(1.0),
+        };
+        let image_scenario = ImageScenario { image: String::from("sample0.jpg"), tracks, focus: None, transition: None };
+        let scenario = Scenario { version: SCENARIO_VERSION, width: 144, height: 144,
+            fps: FrameRate::new(24, 1), duration: 8.0, images: vec![image_scenario], background: Background::default() };
+        let mut json: serde_json::Value = serde_json::from_str(&scenario.to_json().unwrap()).unwrap();
+        let tracks_json = json["images"][0]["tracks"].as_object_mut().unwrap();
+        tracks_json.remove("angle");
+        tracks_json.remove("alpha");
+        let loaded = Scenario::from_json(&json.to_string()).expect("Failed to parse scenario without angle and alpha");
+        assert_eq!(loaded, scenario);
+
+        // the constant default tracks do not move the time the image is shown
+        let tracks = &loaded.images[0].tracks;
+        assert_eq!(tracks.start_time(), 5.0);
+        assert_eq!(tracks.end_time(), 8.0);
+        assert!(loaded.images[0].sample(1.0).is_none());
+        let point = loaded.images[0].sample(6.0).expect("Image is not shown");
+        assert_eq!((point.angle, point.alpha), (0.0, 1.0));
+    }
+
+    #[test]
+    fn test_track_value_spaces() {
+        let keys = |values: &[(f64, f64)]| values.iter()
+            .map(|&(time, value)| Keyframe { time, value, tangent: None, easing: Easing::Linear })
+            .collect::<Vec<Keyframe>>();
+        let scale = Track::new(Interpolation::Linear, keys(&[(0.0, 1.0), (1.0, 4.0)])).with_space(ValueSpace::Logarithmic);
+        assert!((scale.sample(0.5) - 2.0).abs() < 1e-12);
+        let angle = Track::new(Interpolation::Linear, keys(&[(0.0, 3.0), (1.0, -3.0)])).with_space(ValueSpace::ShortestArc);
+        assert!((angle.sample(0.5) - std::f64::consts::PI).abs() < 1e-12);
+        assert!((angle.sample(1.0) - (2.0 * std::f64::consts::PI - 3.0)).abs() < 1e-12);
+
+        let invalid = Track::new(Interpolation::Linear, keys(&[(0.0, 0.0), (1.0, 4.0)])).with_space(ValueSpace::Logarithmic);
+        assert!(invalid.validate().is_err());
+    }
+
+    #[test]
+    fn test_frame_rate() {
+        let ntsc = FrameRate::from_fps(29.97).unwrap();
+        assert_eq!(ntsc, FrameRate::new(30000, 1001));
+        assert_eq!(FrameRate::from_fps(ntsc.fps()).unwrap(), ntsc);
+        assert_eq!(FrameRate::from_fps(25.0).unwrap(), FrameRate::new(25, 1));
+        assert_eq!(FrameRate::from_fps(23.976).unwrap(), FrameRate::new(24000, 1001));
+        assert!(FrameRate::from_fps(0.0).is_err());
+        assert_eq!(ntsc.frame_count(10.0), 300);
+        assert!((ntsc.frame_time(30) - 1.001).abs() < 1e-12);
+        assert_eq!(FrameRate::new(60, 1).frame_count(5.0), 300);
+    }
+
+    #[test]
+    fn test_generator_durations() {
+        let files = vec![String::from("sample0.jpg"), String::from("sample1.jpg")];
+        let images_map = RenkiCore::load_images(&files).expect("Failed to load images");
+        let options = GeneratorOptions {
+            width: 1920, height: 1080, durations: ImageDurations::List(vec![4.0, 6.0]), overlap: 0.5,
+            ..GeneratorOptions::default()
+        };
+        let scenario = Scenario::generate_scenario(&files, &images_map, &options).expect("Failed to generate scenario");
+        assert_eq!(scenario.image_durations(), vec![4.0, 6.0]);
+        assert_eq!(scenario.images[1].tracks.start_time(), 3.5);
+        assert_eq!(scenario.duration, 9.5);
+
+        let options = GeneratorOptions { lead_in: 2.0, lead_out: 3.0, ..options };
+        let scenario = Scenario::generate_scenario(&files, &images_map, &options).expect("Failed to generate scenario");
+        assert_eq!(scenario.image_durations(), vec![4.0, 6.0]);
+        assert_eq!(scenario.duration, 14.5);
+        let repeated = GeneratorOptions { durations: ImageDurations::List(scenario.image_durations()), ..options.clone() };
+        assert_eq!(Scenario::generate_scenario(&files, &images_map, &repeated).expect("Failed to generate scenario"), scenario);
+        let first = &scenario.images[0].tracks;
+        assert_eq!(first.alpha.keys[0].value, 1.0);
+        assert_eq!(first.alpha.keys[1].value, 1.0);
+        let last = &scenario.images[1].tracks;
+        let count = last.alpha.keys.len();
+        assert_eq!(last.alpha.keys[count - 2].value, 1.0);
+        assert_eq!(last.alpha.keys[count - 1].value, 1.0);
+        assert!((first.scale.sample(1.0) - first.scale.keys[1].value).abs() < 1e-9);
+
+        let options = GeneratorOptions { durations: ImageDurations::Weighted(12.0), ..options };
+        let scenario = Scenario::generate_scenario(&files, &images_map, &options).expect("Failed to generate scenario");
+        assert!((scenario.duration - 12.0).abs() < 1e-9);
+
+        let options = GeneratorOptions { durations: ImageDurations::List(vec![4.0]), ..options };
+        assert!(Scenario::generate_scenario(&files, &images_map, &options).is_err());
+    }
+
+    #[test]
+    fn test_generator_transition_alpha() {
+        let files: Vec<String> = ["a.png", "b.png", "c.png"].iter().map(|name| String::from(*name)).collect();
+        let images_map: HashMap<String, RenkiImage> = files.iter().map(|name| (name.clone(), blank_image(64, 36))).collect();
+        let options = GeneratorOptions {
+            width: 64, height: 36, durations: ImageDurations::List(vec![4.0, 4.0, 4.0]), overlap: 1.0,
+            transition: Some(Transition::new(TransitionKind::Crossfade)), ..GeneratorOptions::default()
+        };
+        let scenario = Scenario::generate_scenario(&files, &images_map, &options).expect("Failed to generate scenario");
+        let alpha = |index: usize, time: f64| scenario.images[index].sample(time).expect("Image is not shown").alpha;
+        for index in 1..files.len() {
+            // the incoming image stays opaque after its transition ends, the outgoing one before it starts
+            let end = scenario.images[index - 1].tracks.end_time();
+            assert!((alpha(index, end + 1e-3) - 1.0).abs() < 1e-3);
+            let start = scenario.images[index].tracks.start_time();
+            assert!((alpha(index - 1, start - 1e-3) - 1.0).abs() < 1e-3);
+        }
+
+        let options = GeneratorOptions { transition: None, ..options };
+        let scenario = Scenario::generate_scenario(&files, &images_map, &options).expect("Failed to generate scenario");
+        assert_eq!(scenario.images[1].tracks.alpha.keys[1].value, options.preset.settle_alpha);
+    }
+
+    fn blank_image(width: usize, height: usize) -> RenkiImage {
+        RenkiImage::new(width, height, vec![vec![0_f32; width * height]; 3], vec![1_f32; width * height])
+    }
+
+    #[test]
+    fn test_generator_aspect_motion() {
+        let cases = [
+            ((3000, 1000), (1920, 1080)),
+            ((1000, 3000), (1920, 1080)),
+            ((1920, 1080), (1920, 1080)),
+            ((1600, 1000), (1080, 1920)),
+            ((1000, 1600), (1080, 1920)),
+        ];
+        for ((image_width, image_height), (width, height)) in cases {
+            let files = vec![String::from("image.jpg")];
+            let mut images_map = HashMap::new();
+            images_map.insert(files[0].clone(), blank_image(image_width, image_height));
+            let options = GeneratorOptions { width, height, ..GeneratorOptions::default() };
+            let scenario = Scenario::generate_scenario(&files, &images_map, &options).expect("Failed to generate scenario");
+            let tracks = &scenario.images[0].tracks;
+            let (start, end) = (1, 3);
+            let dx = tracks.anchor_x.keys[end].value - tracks.anchor_x.keys[start].value;
+            let dy = tracks.anchor_y.keys[end].value - tracks.anchor_y.keys[start].value;
+            let frame_aspect = width as f64 / height as f64;
+            let image_aspect = image_width as f64 / image_height as f64;
+            if image_aspect > frame_aspect * 1.05 {
+                assert!(dx > 0.0 && dy == 0.0, "{}x{} in {}x{} should pan right", image_width, image_height, width, height);
+            } else if image_aspect < frame_aspect / 1.05 {
+                assert!(dy > 0.0 && dx == 0.0, "{}x{} in {}x{} should pan down", image_width, image_height, width, height);
+            } else {
+                assert!(dx == 0.0 && dy == 0.0, "{}x{} in {}x{} should zoom", image_width, image_height, width, height);
+                assert!(tracks.scale.keys[end].value > tracks.scale.keys[start].value);
+            }
+            for key in [start, end] {
+                let scale = tracks.scale.keys[key].value;
+                let (half_width, half_height) = (width as f64 * 0.5 / scale, height as f64 * 0.5 / scale);
+                let (anchor_x, anchor_y) = (tracks.anchor_x.keys[key].value, tracks.anchor_y.keys[key].value);
+                assert!(anchor_x - half_width >= -1e-9 && anchor_x + half_width <= image_width as f64 + 1e-9);
+                assert!(anchor_y - half_height >= -1e-9 && anchor_y + half_height <= image_height as f64 + 1e-9);
+            }
+        }
+    }
+
+    #[test]
+    fn test_generator_covers_frame() {
+        let files = vec![String::from("wide.jpg"), String::from("landscape.jpg"), String::from("tall.jpg")];
         let mut images_map = HashMap::new();
-        for filename in &files {
-            let image = RenkiImage::from_img(filename).expect("Failed to load image");
-            images_map.insert(filename.clone(), image);
+        images_map.insert(files[0].clone(), blank_image(3000, 1000));
+        images_map.insert(files[1].clone(), blank_image(1000, 1000));
+        images_map.insert(files[2].clone(), blank_image(1000, 3000));
+        let (width, height) = (1920, 1080);
+        let covers = |scenario: &Scenario| scenario.images.iter().all(|image_scenario| {
+            let image = &images_map[&image_scenario.image];
+            let tracks = &image_scenario.tracks;
+            tracks.scale.keys.iter().all(|key| {
+                let time = key.time;
+                let point = ScenarioPoint {
+                    time, anchor_x: tracks.anchor_x.sample(time), anchor_y: tracks.anchor_y.sample(time),
+                    offset_x: tracks.offset_x.sample(time), offset_y: tracks.offset_y.sample(time),
+                    scale: tracks.scale.sample(time), angle: tracks.angle.sample(time), alpha: 1.0,
+                    tangents: None, easing: Easing::Linear,
+                };
+                let inverse = point.matrix().invert().unwrap();
+                [(0.0, 0.0), (width as f64, 0.0), (width as f64, height as f64), (0.0, height as f64)].iter().all(|&(x, y)| {
+                    let corner = inverse.apply(&Point::new(x, y));
+                    corner.x > -1e-6 && corner.x < image.width as f64 + 1e-6 && corner.y > -1e-6 && corner.y < image.height as f64 + 1e-6
+                })
+            })
+        });
+
+        let options = GeneratorOptions { width, height, ..GeneratorOptions::default() };
+        let scenario = Scenario::generate_scenario(&files, &images_map, &options).expect("Failed to generate scenario");
+        assert!(covers(&scenario));
+
+        let image = blank_image(1920, 1080);
+        let point = still_point(0.0, (960.0, 540.0), (960.0, 540.0));
+        assert!((min_cover_scale(&image, width, height, &point).unwrap() - 1.0).abs() < 1e-9);
+        let rotated = ScenarioPoint { angle: 0.075, ..point.clone() };
+        let scale = min_cover_scale(&image, width, height, &rotated).unwrap();
+        let expected = 0.075_f64.cos() + 0.075_f64.sin() * 1920.0 / 1080.0;
+        assert!((scale - expected).abs() < 1e-9);
+        let shifted = ScenarioPoint { anchor_x: 1440.0, ..point };
+        assert!((min_cover_scale(&image, width, height, &shifted).unwrap() - 2.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_matrix_invert() {
+        let matrix = Matrix2d::translate(-100.0, -50.0)
+            .multiply(&Matrix2d::scale(1.25))
+            .multiply(&Matrix2d::rotation(0.125))
+            .multiply(&Matrix2d::translate(128.0, 72.0));
+        let inverse = matrix.invert().expect("Matrix is singular");
+        let point = inverse.apply(&matrix.apply(&Point::new(12.5, -7.0)));
+        assert!((point.x - 12.5).abs() < 1e-9 && (point.y + 7.0).abs() < 1e-9);
+        assert!(Matrix2d::scale(0.0).invert().is_none());
+    }
+
+    #[test]
+    fn test_generator_motion_styles() {
+        let files: Vec<String> = (0..12).map(|index| format!("image{}.jpg", index)).collect();
+        let mut images_map = HashMap::new();
+        for (index, filename) in files.iter().enumerate() {
+            images_map.insert(filename.clone(), blank_image(800 + index * 100, 1000));
+        }
+        let options = GeneratorOptions { width: 1920, height: 1080, seed: Some(42), ..GeneratorOptions::default() };
+        let first = Scenario::generate_scenario(&files, &images_map, &options).expect("Failed to generate scenario");
+        let second = Scenario::generate_scenario(&files, &images_map, &options).expect("Failed to generate scenario");
+        assert_eq!(first, second);
+        let options_other_seed = GeneratorOptions { seed: Some(43), ..options.clone() };
+        let other = Scenario::generate_scenario(&files, &images_map, &options_other_seed).expect("Failed to generate scenario");
+        assert_ne!(first, other);
+
+        let mut styles = HashMap::new();
+        styles.insert(files[3].clone(), MotionStyle::StaticHold);
+        let pinned_options = GeneratorOptions { styles, ..options };
+        let pinned = Scenario::generate_scenario(&files, &images_map, &pinned_options).expect("Failed to generate scenario");
+        for index in 0..files.len() {
+            if index == 3 {
+                let anchor_x = &pinned.images[index].tracks.anchor_x;
+                assert!(anchor_x.keys.iter().all(|key| key.value == anchor_x.keys[0].value));
+            } else {
+                assert_eq!(pinned.images[index], first.images[index]);
+            }
+        }
+
+        for style in MotionStyle::ALL {
+            assert_eq!(style.name().parse::<MotionStyle>(), Ok(style));
         }
-        let scenario = Scenario::generate_scenario(&files, &images_map, 144, 144, 100);
     }
-}
 
-pub struct RenkiCore {}
+    #[test]
+    fn test_generator_presets() {
+        for name in MotionPreset::NAMES {
+            MotionPreset::named(name).expect("Missing preset").validate().expect("Invalid preset");
+        }
+        assert_eq!(MotionPreset::named("classic"), Some(MotionPreset::default()));
+        assert_eq!(MotionPreset::named("unknown"), None);
 
-impl RenkiCore {
-    pub fn render_images(files: &Vec<String>, length: usize) {
+        let custom = MotionPreset::from_json(r#"{"entry_scale": 1.25, "settle_alpha": 1.0}"#).expect("Failed to parse preset");
+        assert_eq!(custom, MotionPreset { entry_scale: 1.25, settle_alpha: 1.0, ..MotionPreset::default() });
+        assert!(MotionPreset::from_json(r#"{"settle_alpha": 1.5}"#).is_err());
+
+        let files = vec![String::from("image.jpg")];
         let mut images_map = HashMap::new();
-        for filename in files {
-            let image = RenkiImage::from_img(filename).expect("Failed to load image");
-            images_map.insert(filename.clone(), image);
+        images_map.insert(files[0].clone(), blank_image(1000, 1000));
+        let entry_scale = |name: &str| {
+            let options = GeneratorOptions {
+                width: 1000, height: 1000, cover_frame: false, preset: MotionPreset::named(name).unwrap(),
+                ..GeneratorOptions::default()
+            };
+            let scenario = Scenario::generate_scenario(&files, &images_map, &options).expect("Failed to generate scenario");
+            scenario.images[0].tracks.scale.keys[0].value
+        };
+        assert!((entry_scale("documentary") - 1.0).abs() < 1e-9);
+        assert!(entry_scale("gentle") < entry_scale("classic"));
+        assert!(entry_scale("classic") < entry_scale("dramatic"));
+    }
+
+    #[test]
+    fn test_generator_focus() {
+        let files = vec![String::from("portrait.jpg"), String::from("landscape.jpg")];
+        let mut images_map = HashMap::new();
+        images_map.insert(files[0].clone(), blank_image(1000, 3000));
+        images_map.insert(files[1].clone(), blank_image(1920, 1080));
+        let mut focus = HashMap::new();
+        focus.insert(files[0].clone(), FocusRegion { x: 300.0, y: 100.0, width: 400.0, height: 300.0 });
+        focus.insert(files[1].clone(), FocusRegion::point(1700.0, 300.0));
+        let options = GeneratorOptions { width: 1920, height: 1080, focus, ..GeneratorOptions::default() };
+        let scenario = Scenario::generate_scenario(&files, &images_map, &options).expect("Failed to generate scenario");
+
+        for (image_scenario, filename) in scenario.images.iter().zip(&files) {
+            let region = &options.focus[filename];
+            assert_eq!(image_scenario.focus.as_ref(), Some(region));
+            // the settled keyframes keep the whole region in the frame
+            for key_index in 1..4 {
+                let time = image_scenario.tracks.alpha.keys[key_index].time;
+                let point = image_scenario.sample(time).expect("Image is not shown");
+                let matrix = point.matrix();
+                for (x, y) in [(region.x, region.y), (region.x + region.width, region.y + region.height)] {
+                    let corner = matrix.apply(&Point::new(x, y));
+                    assert!(corner.x >= -1e-6 && corner.x <= 1920.0 + 1e-6, "x {} at {}", corner.x, time);
+                    assert!(corner.y >= -1e-6 && corner.y <= 1080.0 + 1e-6, "y {} at {}", corner.y, time);
+                }
+            }
+        }
+        // the vertical pan of the portrait image moves up towards its focus, the zoom ends on the focus point
+        let anchor_y = &scenario.images[0].tracks.anchor_y.keys;
+        assert!(anchor_y[3].value < anchor_y[1].value);
+        let anchor_x = &scenario.images[1].tracks.anchor_x.keys;
+        assert!(anchor_x[3].value > anchor_x[1].value);
+
+        let loaded = Scenario::from_json(&scenario.to_json().unwrap()).expect("Failed to load scenario");
+        assert_eq!(loaded.focus_regions(), options.focus);
+
+        let mut outside = options.focus.clone();
+        outside.insert(files[1].clone(), FocusRegion::point(2000.0, 300.0));
+        let options = GeneratorOptions { focus: outside, ..options };
+        assert!(Scenario::generate_scenario(&files, &images_map, &options).is_err());
+    }
+
+    /// Dark image with a noisy bright patch.
+    fn patch_image(width: usize, height: usize, patch_x: usize, patch_y: usize, patch_size: usize) -> RenkiImage {
+        let mut image = blank_image(width, height);
+        for y in patch_y..patch_y + patch_size {
+            for x in patch_x..patch_x + patch_size {
+                let value = if (x / 3 + y / 5) % 2 == 0 { 250.0 } else { 90.0 };
+                image.channels[0][y * width + x] = value;
+                image.channels[1][y * width + x] = value * 0.5;
+            }
+        }
+        image
+    }
+
+    #[test]
+    fn test_saliency() {
+        assert_eq!(salient_region(&blank_image(600, 400)), None);
+
+        let image = patch_image(1200, 800, 100, 80, 200);
+        let region = salient_region(&image).expect("No salient region");
+        assert!(region.x <= 150.0 && region.x + region.width >= 250.0, "{:?}", region);
+        assert!(region.y <= 130.0 && region.y + region.height >= 230.0, "{:?}", region);
+        assert!(region.center_x() < 400.0 && region.center_y() < 300.0, "{:?}", region);
+
+        // the portrait image pans up towards its patch, unless saliency is disabled
+        let files = vec![String::from("portrait.jpg")];
+        let mut images_map = HashMap::new();
+        images_map.insert(files[0].clone(), patch_image(600, 1800, 200, 100, 200));
+        let options = GeneratorOptions { width: 1920, height: 1080, ..GeneratorOptions::default() };
+        let scenario = Scenario::generate_scenario(&files, &images_map, &options).expect("Failed to generate scenario");
+        let anchor_y = &scenario.images[0].tracks.anchor_y.keys;
+        assert!(anchor_y[3].value < anchor_y[1].value);
+        assert!(scenario.images[0].focus.is_some());
+        let options = GeneratorOptions { saliency: false, ..options };
+        let scenario = Scenario::generate_scenario(&files, &images_map, &options).expect("Failed to generate scenario");
+        let anchor_y = &scenario.images[0].tracks.anchor_y.keys;
+        assert!(anchor_y[3].value > anchor_y[1].value);
+        assert!(scenario.images[0].focus.is_none());
+    }
+
+    fn render_at(scenario: &Scenario, time: f64, images_map: &HashMap<String, RenkiImage>) -> RenkiImage {
+        let options = RenderOptions { linear: false, ..RenderOptions::default() };
+        let background = scenario.prepare_background(images_map, &options).expect("Failed to prepare background");
+        scenario.render_frame(time, images_map, &background, &options).expect("Failed to render frame")
+    }
+
+    fn solid_image(width: usize, height: usize, color: [f32; 3]) -> RenkiImage {
+        RenkiImage::new(width, height, color.iter().map(|value| vec![*value; width * height]).collect(), vec![1_f32; width * height])
+    }
+
+    fn still_point(time: f64, anchor: (f64, f64), offset: (f64, f64)) -> ScenarioPoint {
+        ScenarioPoint {
+            time, anchor_x: anchor.0, anchor_y: anchor.1, offset_x: offset.0, offset_y: offset.1, angle: 0.0, scale: 1.0, alpha: 1.0,
+            tangents: None, easing: Easing::Linear,
+        }
+    }
+
+    /// Directory for the files of a test, removed with them when dropped, also when the test fails.
+    struct TempDir(PathBuf);
+
+    impl TempDir {
+        fn new(name: &str) -> TempDir {
+            static NEXT: AtomicUsize = AtomicUsize::new(0);
+            let index = NEXT.fetch_add(1, Ordering::Relaxed);
+            let dir = std::env::temp_dir().join(format!("renki_test_{}_{}_{}", name, std::process::id(), index));
+            std::fs::create_dir_all(&dir).unwrap();
+            TempDir(dir)
+        }
+
+        fn path(&self, name: &str) -> String {
+            self.0.join(name).to_string_lossy().into_owned()
+        }
+    }
+
+    impl Drop for TempDir {
+        fn drop(&mut self) {
+            let _ = std::fs::remove_dir_all(&self.0);
+        }
+    }
+
+    /// Generates from two images saved in `dir` together with the `extra` image the `options` refer to.
+    fn generate_in(dir: &TempDir, extra: &str, options: &GeneratorOptions) -> (HashMap<String, RenkiImage>, Scenario) {
+        let files = vec![dir.path("a.png"), dir.path("b.png")];
+        for file in files.iter().chain([&dir.path(extra)]) {
+            patch_image(96, 64, 30, 20, 16).save(file);
+        }
+        RenkiCore::generate(&files, options).expect("Failed to generate scenario")
+    }
+
+    /// Image shown unscaled from `start` to `end` with its `anchor` at `offset` in the frame.
+    fn still_image(image: &str, start: f64, end: f64, anchor: (f64, f64), offset: (f64, f64), transition: Option<Transition>) -> ImageScenario {
+        let points = [still_point(start, anchor, offset), still_point(end, anchor, offset)];
+        let image_scenario = ImageScenario::from_points(image, &points, &InterpolationSettings::default()).unwrap();
+        ImageScenario { transition, ..image_scenario }
+    }
+
+    #[test]
+    fn test_transitions() {
+        let (width, height) = (40, 20);
+        let center = (20.0, 10.0);
+        let mut images_map = HashMap::new();
+        images_map.insert(String::from("red.png"), solid_image(width, height, [255.0, 0.0, 0.0]));
+        images_map.insert(String::from("blue.png"), solid_image(width, height, [0.0, 0.0, 255.0]));
+        let gradient: Vec<f32> = (0..width * height).map(|index| (index % width) as f32 / width as f32 * 255.0).collect();
+        images_map.insert(String::from("matte.png"), RenkiImage::new(width, height, vec![gradient; 3], vec![1_f32; width * height]));
+        let scenario_with = |transition: Transition| Scenario {
+            version: SCENARIO_VERSION, width, height, fps: FrameRate::default(), duration: 3.0,
+            images: vec![still_image("red.png", 0.0, 2.0, center, center, None), still_image("blue.png", 1.0, 3.0, center, center, Some(transition))],
+            background: Background::default(),
+        };
+        let pixel = |frame: &RenkiImage, x: usize, y: usize| [frame.channels[0][y * width + x], frame.channels[2][y * width + x]];
+
+        let wipe = scenario_with(Transition { softness: 0.0, ..Transition::new(TransitionKind::Wipe { direction: Direction::Right }) });
+        wipe.validate().expect("Invalid scenario");
+        let frame = render_at(&wipe, 1.5, &images_map);
+        assert_eq!(pixel(&frame, 5, 10), [0.0, 255.0]);
+        assert_eq!(pixel(&frame, 35, 10), [255.0, 0.0]);
+        let frame = render_at(&wipe, 1.0, &images_map);
+        assert_eq!(pixel(&frame, 5, 10), [255.0, 0.0]);
+
+        let dip = scenario_with(Transition::new(TransitionKind::Dip { color: [0.0; 3] }));
+        let frame = render_at(&dip, 1.5, &images_map);
+        assert_eq!(pixel(&frame, 20, 10), [0.0, 0.0]);
+        let frame = render_at(&dip, 1.25, &images_map);
+        assert!((pixel(&frame, 20, 10)[0] - 127.5).abs() < 1e-3);
+
+        let push = scenario_with(Transition::new(TransitionKind::Slide { direction: Direction::Left, push: true }));
+        let frame = render_at(&push, 1.5, &images_map);
+        assert_eq!(pixel(&frame, 5, 10), [255.0, 0.0]);
+        assert_eq!(pixel(&frame, 35, 10), [0.0, 255.0]);
+
+        let matte = scenario_with(Transition { softness: 0.0, ..Transition::new(TransitionKind::LumaMatte { image: String::from("matte.png") }) });
+        assert_eq!(matte.image_files(), vec!["red.png", "blue.png", "matte.png"]);
+        let frame = render_at(&matte, 1.5, &images_map);
+        assert_eq!(pixel(&frame, 5, 10), [0.0, 255.0]);
+        assert_eq!(pixel(&frame, 35, 10), [255.0, 0.0]);
+
+        for transition in ["crossfade", "iris", "zoom_through", r#"{"type": "dip", "color": [255, 255, 255], "easing": "ease_in_out"}"#] {
+            let scenario = scenario_with(transition.parse::<Transition>().expect("Failed to parse transition"));
+            let loaded = Scenario::from_json(&scenario.to_json().unwrap()).expect("Failed to load scenario");
+            assert_eq!(loaded, scenario);
+            let frame = render_at(&scenario, 1.0, &images_map);
+            assert_eq!(pixel(&frame, 20, 10), [255.0, 0.0]);
+        }
+        assert!("luma_matte".parse::<Transition>().is_err());
+
+        let mut separate = scenario_with(Transition::new(TransitionKind::Iris));
+        separate.images[1] = still_image("blue.png", 2.0, 3.0, center, center, Some(Transition::new(TransitionKind::Iris)));
+        assert!(separate.validate().is_err());
+
+        let missing = scenario_with(Transition::new(TransitionKind::LumaMatte { image: String::from("missing.png") }));
+        let options = RenderOptions::default();
+        let background = missing.prepare_background(&images_map, &options).expect("Failed to prepare background");
+        let error = missing.render_frame(1.5, &images_map, &background, &options).err().expect("Rendered a missing matte");
+        assert!(error.contains("missing.png"), "{}", error);
+
+        // the generator loads the matte image with the images
+        let dir = TempDir::new("matte");
+        let options = GeneratorOptions {
+            width: 48, height: 32, durations: ImageDurations::Total(2.0), overlap: 0.2,
+            transition: Some(Transition::new(TransitionKind::LumaMatte { image: dir.path("matte.png") })), ..GeneratorOptions::default()
+        };
+        let (images_map, _) = generate_in(&dir, "matte.png", &options);
+        assert!(images_map.contains_key(&dir.path("matte.png")));
+    }
+
+    #[test]
+    fn test_backgrounds() {
+        let (width, height) = (64, 32);
+        let mut images_map = HashMap::new();
+        images_map.insert(String::from("portrait.png"), solid_image(16, 32, [200.0, 100.0, 50.0]));
+        images_map.insert(String::from("background.png"), solid_image(8, 8, [10.0, 20.0, 30.0]));
+        let image_scenario = still_image("portrait.png", 0.0, 2.0, (8.0, 16.0), (32.0, 16.0), None);
+        let scenario_with = |background: Background| Scenario {
+            version: SCENARIO_VERSION, width, height, fps: FrameRate::default(), duration: 2.0,
+            images: vec![image_scenario.clone()], background,
+        };
+        let pixel = |frame: &RenkiImage, x: usize, y: usize| [0, 1, 2].map(|channel| frame.channels[channel][y * width + x]);
+
+        let frame = render_at(&scenario_with(Background::default()), 1.0, &images_map);
+        assert_eq!(pixel(&frame, 2, 2), [0.0, 0.0, 0.0]);
+        assert_eq!(pixel(&frame, 32, 16), [200.0, 100.0, 50.0]);
+
+        let frame = render_at(&scenario_with(Background::Solid { color: [255.0, 255.0, 255.0] }), 1.0, &images_map);
+        assert_eq!(pixel(&frame, 2, 2), [255.0, 255.0, 255.0]);
+
+        let gradient = Background::Gradient { from: [0.0; 3], to: [255.0; 3], angle: 0.0 };
+        let frame = render_at(&scenario_with(gradient), 1.0, &images_map);
+        assert!(pixel(&frame, 0, 2)[0] < 5.0 && pixel(&frame, 63, 2)[0] > 250.0);
+
+        let scenario = scenario_with(Background::Image { image: String::from("background.png") });
+        assert_eq!(scenario.image_files(), vec!["portrait.png", "background.png"]);
+        let frame = render_at(&scenario, 1.0, &images_map);
+        assert_eq!(pixel(&frame, 2, 2), [10.0, 20.0, 30.0]);
+
+        let scenario = scenario_with("blur".parse::<Background>().expect("Failed to parse background"));
+        let frame = render_at(&scenario, 1.0, &images_map);
+        for (channel, value) in pixel(&frame, 2, 2).iter().zip([200.0, 100.0, 50.0]) {
+            assert!((channel - value * 0.7).abs() < 1e-3);
+        }
+        let loaded = Scenario::from_json(&scenario.to_json().unwrap()).expect("Failed to load scenario");
+        assert_eq!(loaded, scenario);
+        assert!(r#"{"type": "blur", "brightness": 2.0}"#.parse::<Background>().is_err());
+
+        let missing = scenario_with(Background::Image { image: String::from("missing.png") });
+        let error = missing.prepare_background(&images_map, &RenderOptions::default()).err().expect("Prepared a missing background image");
+        assert!(error.contains("missing.png"), "{}", error);
+
+        // the generator loads the background image with the images
+        let dir = TempDir::new("background");
+        let options = GeneratorOptions {
+            width: 48, height: 32, durations: ImageDurations::Total(2.0), overlap: 0.2,
+            background: Background::Image { image: dir.path("bg.png") }, ..GeneratorOptions::default()
+        };
+        let (images_map, scenario) = generate_in(&dir, "bg.png", &options);
+        assert!(images_map.contains_key(&dir.path("bg.png")));
+        assert!(scenario.prepare_background(&images_map, &RenderOptions::default()).is_ok());
+    }
+
+    #[test]
+    fn test_resamplers() {
+        let image = RenkiImage::from_img("sample0.jpg").expect("Failed to load file");
+        let (width, height) = (256, 144);
+        let mean_difference = |first: &RenkiImage, second: &RenkiImage| {
+            let mut sum = 0.0;
+            let mut count = 0;
+            for (first_channel, second_channel) in first.channels.iter().zip(&second.channels) {
+                for index in 0..width * height {
+                    if first.alpha[index] > 0.999 && second.alpha[index] > 0.999 {
+                        sum += (first_channel[index] - second_channel[index]).abs() as f64;
+                        count += 1;
+                    }
+                }
+            }
+            sum / count as f64
+        };
+        for (scale, angle) in [(1.25, 0.125), (0.3, -0.05), (1.0, 0.0)] {
+            let matrix = Matrix2d::translate(-(image.width as f64) * 0.5, -(image.height as f64) * 0.5)
+                .multiply(&Matrix2d::scale(scale))
+                .multiply(&Matrix2d::rotation(angle))
+                .multiply(&Matrix2d::translate(width as f64 * 0.5, height as f64 * 0.5));
+            let area = image.transform(&matrix, width, height, 1.0);
+            for resampler in [Resampler::Nearest, Resampler::Bilinear, Resampler::Bicubic, Resampler::Lanczos3] {
+                let resampled = image.transform_with(&matrix, width, height, 1.0, resampler);
+                let difference = mean_difference(&area, &resampled);
+                assert!(difference < 5.0, "{:?} at scale {} differs by {}", resampler, scale, difference);
+            }
+        }
+        for resampler in Resampler::ALL {
+            assert_eq!(resampler.name().parse::<Resampler>(), Ok(resampler));
         }
-        let scenario = Scenario::generate_scenario(&files, &images_map, 1080, 1920, length);
-        scenario.render(&images_map, "frames/");
+
+        // frame pixel centers on source pixel boundaries
+        let pattern = RenkiImage::new(8, 8, vec![(0..64).map(|index| index as f32).collect(); 3], vec![1_f32; 64]);
+        for (offset_x, offset_y) in [(0.5, 0.5), (0.5, 0.0), (0.0, 0.5)] {
+            let matrix = Matrix2d::translate(offset_x, offset_y);
+            for resampler in [Resampler::Nearest, Resampler::Bilinear, Resampler::Bicubic, Resampler::Lanczos3] {
+                let shifted = pattern.transform_with(&matrix, 8, 8, 1.0, resampler);
+                for y in 3..5 {
+                    for x in 3..5 {
+                        assert!(shifted.alpha[y * 8 + x] > 0.999, "{:?} at offset {},{}", resampler, offset_x, offset_y);
+                    }
+                }
+            }
+            let nearest = pattern.transform_with(&matrix, 8, 8, 1.0, Resampler::Nearest);
+            assert_eq!(nearest.channels[0][3 * 8 + 3], 3.0 * 8.0 + 3.0 - if offset_y > 0.0 { 8.0 } else { 0.0 } - if offset_x > 0.0 { 1.0 } else { 0.0 });
+        }
+    }
+
+    #[test]
+    fn test_mipmaps() {
+        let image = RenkiImage::from_img("sample0.jpg").expect("Failed to load file");
+        assert!(image.mipmaps.is_empty());
+        let images_map = HashMap::from([(String::from("sample0.jpg"), image)]);
+        let images_map = RenkiCore::decode_images(images_map, &RenderOptions::default());
+        let image = &images_map["sample0.jpg"];
+        assert_eq!(image.mipmaps[0].channels, image.half_size().channels);
+        let mut size = (image.width, image.height);
+        for level in &image.mipmaps {
+            size = (size.0.div_ceil(2), size.1.div_ceil(2));
+            assert_eq!((level.width, level.height), size);
+        }
+        assert!(image.mipmaps.last().is_some_and(|level| level.width.min(level.height) < 32));
+
+        let mut solid = solid_image(7, 5, [100.0, 50.0, 25.0]);
+        solid.build_mipmaps();
+        assert!(solid.mipmaps.is_empty());
+        let mut half = solid_image(64, 40, [100.0, 50.0, 25.0]);
+        half.build_mipmaps();
+        assert_eq!((half.mipmaps[0].width, half.mipmaps[0].height), (32, 20));
+        assert!(half.mipmaps[0].channels[0].iter().all(|value| *value == 100.0));
+
+        let (width, height) = (64, 36);
+        for scale in [0.3, 0.12, 0.05] {
+            let matrix = || Matrix2d::translate(-(image.width as f64) * 0.5, -(image.height as f64) * 0.5)
+                .multiply(&Matrix2d::scale(scale))
+                .multiply(&Matrix2d::rotation(0.05))
+                .multiply(&Matrix2d::translate(width as f64 * 0.5, height as f64 * 0.5));
+            let (level, level_matrix) = image.mipmap_level(matrix());
+            assert!(level.width < image.width);
+            assert!((level_matrix.average_scale() * level.width as f64 / image.width as f64 - scale).abs() < scale * 0.05);
+            for resampler in [Resampler::Area, Resampler::Bilinear] {
+                let full = image.transform_with(&matrix(), width, height, 1.0, resampler);
+                let mipmapped = level.transform_with(&level_matrix, width, height, 1.0, resampler);
+                let mut difference = 0.0;
+                for (full, mipmapped) in full.channels.iter().zip(&mipmapped.channels) {
+                    difference += full.iter().zip(mipmapped).map(|(a, b)| (a - b).abs() as f64).sum::<f64>();
+                }
+                difference /= (width * height * 3) as f64;
+                assert!(difference < 4.0, "{:?} at scale {} differs by {}", resampler, scale, difference);
+            }
+        }
+    }
+
+    #[test]
+    fn test_parallel_render() {
+        let files = vec![String::from("first.png"), String::from("second.png")];
+        let images_map = HashMap::from([
+            (files[0].clone(), patch_image(160, 90, 40, 20, 30)),
+            (files[1].clone(), patch_image(120, 120, 70, 50, 20)),
+        ]);
+        let options = GeneratorOptions { width: 64, height: 36, durations: ImageDurations::Total(1.0), overlap: 0.2, ..GeneratorOptions::default() };
+        let scenario = Scenario::generate_scenario(&files, &images_map, &options).expect("Failed to generate scenario");
+
+        let render_frames = |name: &str, render_options: &RenderOptions| {
+            let dir = TempDir::new(&format!("parallel_{}", name));
+            scenario.render(&images_map, &dir.path(""), render_options).expect("Failed to render");
+            (0..scenario.frame_count())
+                .map(|frame_index| std::fs::read(dir.path(&format!("frame{:04}.png", frame_index))).expect("Missing frame"))
+                .collect::<Vec<Vec<u8>>>()
+        };
+        let single = render_frames("single", &RenderOptions { threads: 1, ..RenderOptions::default() });
+        let parallel = render_frames("parallel", &RenderOptions { threads: 4, ..RenderOptions::default() });
+        assert_eq!(single.len(), 25);
+        assert_eq!(single, parallel);
+
+        let background = scenario.prepare_background(&images_map, &RenderOptions::default()).expect("Failed to prepare background");
+        for resampler in [Resampler::Area, Resampler::Bilinear] {
+            let single = RenderOptions { resampler, threads: 1, ..RenderOptions::default() };
+            let tiled = RenderOptions { threads: 3, tiles: true, ..single.clone() };
+            let frame = scenario.render_frame(0.3, &images_map, &background, &single).expect("Failed to render frame");
+            let tiled_frame = scenario.render_frame(0.3, &images_map, &background, &tiled).expect("Failed to render frame");
+            assert_eq!(frame.channels, tiled_frame.channels);
+        }
+
+        // area bands of rotated and axis aligned transforms
+        let image = &images_map[&files[0]];
+        for matrix in [Matrix2d::scale(0.45).multiply(&Matrix2d::rotation(0.2)).multiply(&Matrix2d::translate(5.0, -3.0)),
+                       Matrix2d::scale(0.45).multiply(&Matrix2d::translate(5.0, -3.0))] {
+            let single = image.transform_threaded(&matrix, 64, 36, 0.9, Resampler::Area, 1);
+            let banded = image.transform_threaded(&matrix, 64, 36, 0.9, Resampler::Area, 5);
+            assert_eq!(single.channels, banded.channels);
+            assert_eq!(single.alpha, banded.alpha);
+        }
+    }
+
+    #[test]
+    fn test_transform_culling() {
+        let mut image = patch_image(64, 48, 20, 10, 16);
+        image.channels[1] = (0..64 * 48).map(|index| (index % 251) as f32).collect();
+        let matrix = Matrix2d::translate(-32.0, -24.0)
+            .multiply(&Matrix2d::scale(1.5))
+            .multiply(&Matrix2d::rotation(0.3))
+            .multiply(&Matrix2d::translate(20.0, 15.0));
+        // on a canvas covering the whole transformed image nothing is culled, the frame is a window of it
+        let (offset_x, offset_y) = (60, 50);
+        let canvas = image.transform(&matrix.multiply(&Matrix2d::translate(offset_x as f64, offset_y as f64)), 160, 140, 0.8);
+        let frame = image.transform(&matrix, 40, 30, 0.8);
+        for y in 0..30 {
+            for x in 0..40 {
+                let canvas_index = (y + offset_y) * 160 + x + offset_x;
+                for (frame_channel, canvas_channel) in frame.channels.iter().zip(canvas.channels.iter()) {
+                    assert!((frame_channel[y * 40 + x] - canvas_channel[canvas_index]).abs() < 1e-3);
+                }
+                assert!((frame.alpha[y * 40 + x] - canvas.alpha[canvas_index]).abs() < 1e-5);
+            }
+        }
+        assert!(frame.alpha.iter().all(|alpha| (alpha - 0.8).abs() < 1e-4));
+
+        let outside = image.transform(&Matrix2d::translate(500.0, 0.0), 40, 30, 1.0);
+        assert!(outside.alpha.iter().all(|alpha| *alpha == 0.0));
+        let invisible = image.transform(&matrix, 40, 30, 0.0);
+        assert!(invisible.alpha.iter().chain(invisible.channels.iter().flatten()).all(|value| *value == 0.0));
+    }
+
+    #[test]
+    fn test_axis_aligned_transform() {
+        let mut image = patch_image(50, 40, 12, 8, 10);
+        image.channels[2] = (0..50 * 40).map(|index| (index * 7 % 255) as f32).collect();
+        for (scale, translate_x, translate_y) in [(1.0, 0.0, 0.0), (1.5, -10.3, -7.8), (0.7, 3.25, 1.5), (0.37, 5.0, -0.4)] {
+            let matrix = Matrix2d::scale(scale).multiply(&Matrix2d::translate(translate_x, translate_y));
+            // a tiny rotation takes the general polygon clipping path
+            let rotated = Matrix2d::rotation(1e-12).multiply(&matrix);
+            assert!(matrix.axis_aligned().is_some() && rotated.axis_aligned().is_none());
+            let fast = image.transform(&matrix, 36, 30, 0.9);
+            let reference = image.transform(&rotated, 36, 30, 0.9);
+            for (fast_channel, reference_channel) in fast.channels.iter().chain([&fast.alpha]).zip(reference.channels.iter().chain([&reference.alpha])) {
+                for (fast_value, reference_value) in fast_channel.iter().zip(reference_channel) {
+                    assert!((fast_value - reference_value).abs() < 1e-2, "scale {}: {} != {}", scale, fast_value, reference_value);
+                }
+            }
+        }
+        let identity = image.transform(&Matrix2d::scale(1.0), 50, 40, 1.0);
+        assert_eq!(identity.channels, image.channels);
+    }
+
+    #[test]
+    fn test_linear_light() {
+        for value in [0.0, 10.0, 100.0, 188.0, 255.0] {
+            assert!((color::linear_to_srgb(color::srgb_to_linear(value)) - value).abs() < 1e-2);
+        }
+        let (width, height) = (8, 4);
+        let center = (4.0, 2.0);
+        let images_map = HashMap::from([
+            (String::from("black.png"), solid_image(width, height, [0.0; 3])),
+            (String::from("white.png"), solid_image(width, height, [255.0; 3])),
+        ]);
+        let scenario = Scenario {
+            version: SCENARIO_VERSION, width, height, fps: FrameRate::default(), duration: 3.0,
+            images: vec![still_image("black.png", 0.0, 2.0, center, center, None),
+                         still_image("white.png", 1.0, 3.0, center, center, Some(Transition::new(TransitionKind::Crossfade)))],
+            background: Background::default(),
+        };
+        let crossfade = |linear: bool| {
+            let options = RenderOptions { linear, ..RenderOptions::default() };
+            let images_map: HashMap<String, RenkiImage> = images_map.iter()
+                .map(|(name, image)| (name.clone(), if linear { image.to_linear() } else { image.clone() }))
+                .collect();
+            let background = scenario.prepare_background(&images_map, &options).expect("Failed to prepare background");
+            let frame = scenario.render_frame(1.5, &images_map, &background, &options).expect("Failed to render frame");
+            if linear { frame.to_srgb() } else { frame }
+        };
+        // half of the light of white is perceived as mid-gray, sRGB 188
+        let linear = crossfade(true);
+        assert!(linear.channels.iter().flatten().all(|value| (value - 188.0).abs() < 1.0));
+        let srgb = crossfade(false);
+        assert!(srgb.channels.iter().flatten().all(|value| (value - 127.5).abs() < 1e-3));
+
+        // averaging black and white pixels on a downscale gives the same mid-gray
+        let stripes: Vec<f32> = (0..16 * 16).map(|index| if index % 2 == 0 { 0.0 } else { 255.0 }).collect();
+        let stripes = RenkiImage::new(16, 16, vec![stripes; 3], vec![1_f32; 16 * 16]);
+        let downscaled = stripes.to_linear().transform(&Matrix2d::scale(0.5), 8, 8, 1.0).to_srgb();
+        assert!(downscaled.channels.iter().flatten().all(|value| (value - 188.0).abs() < 1.0));
+    }
+
+    #[test]
+    fn test_dithering() {
+        // rounding instead of truncation
+        let image = solid_image(4, 4, [0.6, 127.5, 254.7]);
+        assert_eq!(image.to_rgb8(Dither::None, 0)[..3], [1, 128, 255]);
+        let clipped = solid_image(2, 2, [-3.0, 300.0, 99.49]);
+        assert_eq!(clipped.to_rgb8(Dither::None, 5)[..3], [0, 255, 99]);
+
+        // a level between two steps is dithered to them in proportion, frames use other patterns
+        let (width, height) = (64, 64);
+        let flat = solid_image(width, height, [100.25; 3]);
+        for dither in [Dither::Ordered, Dither::BlueNoise] {
+            let first = flat.to_rgb8(dither, 0);
+            assert!(first.iter().all(|value| *value == 100 || *value == 101));
+            let mean = first.iter().map(|value| *value as f64).sum::<f64>() / first.len() as f64;
+            assert!((mean - 100.25).abs() < 0.01, "{} mean {}", dither.name(), mean);
+            assert_ne!(first, flat.to_rgb8(dither, 1));
+            assert_eq!(first, flat.to_rgb8(dither, 0));
+            assert_eq!(dither.name().parse::<Dither>(), Ok(dither));
+        }
+
+        // blue noise has no low frequencies: the quarter of a level set in every 8x8 block stays close to a quarter
+        let pattern = flat.to_rgb8(Dither::BlueNoise, 0);
+        for block_y in 0..height / 8 {
+            for block_x in 0..width / 8 {
+                let set = (0..64).filter(|index| pattern[((block_y * 8 + index / 8) * width + block_x * 8 + index % 8) * 3] == 101).count();
+                assert!((8..=24).contains(&set), "block {},{} has {} set pixels", block_x, block_y, set);
+            }
+        }
+    }
+
+    #[test]
+    fn test_output_formats() {
+        let dir = TempDir::new("output");
+        let mut frame = solid_image(3, 2, [100.3, 0.0, 255.0]);
+        frame.channels[1][5] = 50.0;
+        let path = |format: OutputFormat| dir.path(&format!("frame.{}", format.extension()));
+        let save = |format: OutputFormat, linear: bool| {
+            frame.save_frame(&path(format), &RenderOptions { output: format, linear, ..RenderOptions::default() }, 0);
+        };
+
+        for format in [OutputFormat::Png16, OutputFormat::Tiff16] {
+            save(format, false);
+            let saved = image::open(path(format)).expect("Failed to read frame").to_rgb16();
+            assert_eq!(saved.get_pixel(0, 0).0, [25777, 0, 65535]);
+            assert_eq!(saved.get_pixel(2, 1).0, [25777, 12850, 65535]);
+            assert_eq!(format.name().parse::<OutputFormat>(), Ok(format));
+        }
+        // 16-bit frames are encoded to sRGB, float frames keep the linear values
+        save(OutputFormat::Png16, true);
+        let encoded = image::open(path(OutputFormat::Png16)).expect("Failed to read frame").to_rgb16();
+        assert_eq!(encoded.get_pixel(2, 1).0[1], (color::linear_to_srgb(50.0) / 255.0 * 65535.0).round() as u16);
+        save(OutputFormat::Exr, true);
+        let saved = image::open(path(OutputFormat::Exr)).expect("Failed to read frame").to_rgb32f();
+        assert_eq!(saved.dimensions(), (3, 2));
+        assert!((saved.get_pixel(2, 1).0[0] - 100.3 / 255.0).abs() < 1e-6);
+        assert!((saved.get_pixel(2, 1).0[1] - 50.0 / 255.0).abs() < 1e-6);
+
+        save(OutputFormat::Pfm, true);
+        let pfm = std::fs::read(path(OutputFormat::Pfm)).expect("Failed to read frame");
+        let header = b"PF\n3 2\n-1.0\n";
+        assert_eq!(&pfm[..header.len()], header);
+        assert_eq!(pfm.len(), header.len() + 3 * 2 * 3 * 4);
+        // the bottom row comes first
+        let value = |index: usize| f32::from_le_bytes(pfm[header.len() + index * 4..header.len() + index * 4 + 4].try_into().unwrap());
+        assert!((value(7) - 50.0 / 255.0).abs() < 1e-6);
+        assert_eq!(value(8), 1.0);
+    }
+
+    #[test]
+    fn test_alpha() {
+        let dir = TempDir::new("alpha");
+        let path = |name: &str| dir.path(name);
+
+        // a 4x8 logo: opaque red left half, transparent blue right half
+        let (width, height) = (4, 8);
+        let pixels: Vec<u8> = (0..width * height)
+            .flat_map(|index| if index % width < 2 { [255, 0, 0, 255] } else { [0, 0, 255, 0] })
+            .collect();
+        image::save_buffer(path("logo.png"), &pixels, width as u32, height as u32, image::ColorType::Rgba8).unwrap();
+        let logo = RenkiImage::from_img(&path("logo.png")).expect("Failed to load logo");
+        assert_eq!(logo.alpha[..4], [1.0, 1.0, 0.0, 0.0]);
+
+        // transparent pixels do not bleed their color into the resampled image
+        for resampler in [Resampler::Area, Resampler::Bilinear] {
+            // the first frame column covers the last red and the first blue column
+            let halved = logo.transform_with(&Matrix2d::translate(-1.0, 0.0).multiply(&Matrix2d::scale(0.5)), 2, 4, 1.0, resampler);
+            assert!((halved.alpha[2] - 0.5).abs() < 1e-4, "{} alpha {}", resampler.name(), halved.alpha[2]);
+            assert!((halved.channels[0][2] - 255.0).abs() < 1e-3 && halved.channels[2][2].abs() < 1e-3);
+        }
+        let rotated = logo.transform(&Matrix2d::rotation(1e-12), width, height, 0.5);
+        assert!((rotated.alpha[0] - 0.5).abs() < 1e-4 && rotated.alpha[3].abs() < 1e-4);
+        let mipmap_source = RenkiImage::new(64, 64, vec![(0..64 * 64).map(|index| if index % 2 == 0 { 200.0 } else { 0.0 }).collect(); 3],
+                                            (0..64 * 64).map(|index| if index % 2 == 0 { 1.0 } else { 0.0 }).collect());
+        let mipmap = mipmap_source.half_size();
+        assert!((mipmap.channels[0][0] - 200.0).abs() < 1e-3 && (mipmap.alpha[0] - 0.5).abs() < 1e-6);
+
+        // over an opaque background the frame stays opaque, over a transparent one it keeps the logo alpha
+        let background = solid_image(width, height, [0.0, 255.0, 0.0]);
+        let over_opaque = background.blend(&rotated);
+        assert_eq!(over_opaque.alpha, vec![1.0; width * height]);
+        assert!((over_opaque.channels[0][0] - 127.5).abs() < 1e-2 && over_opaque.channels[1][3] == 255.0);
+        let transparent = RenkiImage::new(width, height, vec![vec![0.0; width * height]; 3], vec![0.0; width * height]);
+        let over_transparent = transparent.blend(&rotated);
+        assert!((over_transparent.alpha[0] - 0.5).abs() < 1e-4 && over_transparent.alpha[3] == 0.0);
+        assert!((over_transparent.channels[0][0] - 255.0).abs() < 1e-2);
+
+        let scenario = Scenario {
+            version: SCENARIO_VERSION, width, height, fps: FrameRate::default(), duration: 1.0,
+            images: vec![still_image("logo.png", 0.0, 1.0, (2.0, 2.0), (2.0, 2.0), None)],
+            background: Background::Solid { color: [0.0, 255.0, 0.0] },
+        };
+        let images_map = HashMap::from([(String::from("logo.png"), logo.to_linear())]);
+        let options = RenderOptions { transparent: true, threads: 1, ..RenderOptions::default() };
+        let background = scenario.prepare_background(&images_map, &options).expect("Failed to prepare background");
+        let frame = scenario.render_frame(0.5, &images_map, &background, &options).expect("Failed to render frame");
+        frame.save_frame(&path("frame.png"), &options, 0);
+        let saved = image::open(path("frame.png")).expect("Failed to read frame").to_rgba8();
+        assert_eq!(saved.get_pixel(0, 1).0, [255, 0, 0, 255]);
+        assert_eq!(saved.get_pixel(3, 1).0[3], 0);
+    }
+
+    #[test]
+    fn test_focus_sidecars() {
+        let dir = TempDir::new("sidecars");
+        let files = vec![dir.path("a.png"), dir.path("b.png")];
+        for file in &files {
+            patch_image(96, 64, 30, 20, 16).save(file);
+        }
+        let focus = FocusRegion { x: 10.0, y: 8.0, width: 20.0, height: 16.0 };
+        std::fs::write(FocusRegion::sidecar_path(&files[0]), serde_json::to_string(&focus).unwrap()).unwrap();
+        assert_eq!(RenkiCore::scan_images(&dir.path("")).expect("Failed to scan directory"), files);
+
+        let options = GeneratorOptions {
+            width: 48, height: 32, durations: ImageDurations::Total(2.0), overlap: 0.2, saliency: false,
+            ..GeneratorOptions::default()
+        };
+        let (_, scenario) = RenkiCore::generate(&files, &options).expect("Failed to generate scenario");
+        assert_eq!(scenario.images[0].focus, Some(focus));
+        assert_eq!(scenario.images[1].focus, None);
+    }
+
+    #[test]
+    fn test_export_paths() {
+        let dir = TempDir::new("export");
+        std::fs::create_dir_all(dir.path("photos")).unwrap();
+        std::fs::create_dir_all(dir.path("out")).unwrap();
+        let files: Vec<String> = ["a.png", "b.png"].iter().map(|name| dir.path(&format!("photos/{}", name))).collect();
+        for file in &files {
+            patch_image(96, 64, 30, 20, 16).save(file);
+        }
+        let scenario_path = dir.path("out/s.json");
+        let options = GeneratorOptions { width: 48, height: 32, durations: ImageDurations::Total(2.0), overlap: 0.2, ..GeneratorOptions::default() };
+        RenkiCore::export_scenario(&files, &options, &scenario_path).expect("Failed to export scenario");
+
+        let scenario = Scenario::load(&scenario_path).expect("Failed to load scenario");
+        assert_eq!(scenario.image_files(), vec!["../photos/a.png", "../photos/b.png"]);
+        let images_map = RenkiCore::load_scenario_images(&scenario, &dir.0.join("out")).expect("Failed to load scenario images");
+        assert_eq!(images_map.len(), 2);
+
+        let missing = RenkiCore::load_scenario_images(&scenario, &dir.0).err().expect("Loaded images from the wrong directory");
+        assert!(missing.contains("photos/a.png"), "{}", missing);
+
+        // focus regions read back from the scenario apply to the images named by their own paths
+        let focus = FocusRegion { x: 10.0, y: 8.0, width: 20.0, height: 16.0 };
+        let options = GeneratorOptions { focus: HashMap::from([(files[0].clone(), focus.clone())]), saliency: false, ..options };
+        RenkiCore::export_scenario(&files, &options, &scenario_path).expect("Failed to export scenario");
+        let focus_regions = RenkiCore::focus_regions(&scenario_path).expect("Failed to read focus regions");
+        assert!(focus_regions.keys().all(|path| path.contains("..")));
+        let options = GeneratorOptions { focus: focus_regions, ..options };
+        let (_, scenario) = RenkiCore::generate(&files, &options).expect("Failed to generate scenario");
+        assert_eq!(scenario.images[0].focus, Some(focus));
+        assert_eq!(scenario.images[1].focus, None);
     }
 }
@@ -44,13 +44,47 @@ impl Matrix2d {
 
     pub fn multiply(&self, m: &Matrix2d) -> Matrix2d {
         let mut n = vec!(vec!(0_f64, 0_f64, 0_f64), vec!(0_f64, 0_f64, 0_f64), vec!(0_f64, 0_f64, 0_f64));
-        for i in 0..3 {
-            for j in 0..3 {
+        for (i, row) in n.iter_mut().enumerate() {
+            for (j, cell) in row.iter_mut().enumerate() {
                 for k in 0..3 {
-                    n[i][j] += m.data[i][k] * self.data[k][j]
+                    *cell += m.data[i][k] * self.data[k][j]
                 }
             }
         }
         Matrix2d { data: n }
     }
+
+    /// Average scale of the transform, the square root of the area a unit square is transformed to.
+    pub fn average_scale(&self) -> f64 {
+        (self.data[0][0] * self.data[1][1] - self.data[0][1] * self.data[1][0]).abs().sqrt()
+    }
+
+    /// Scale and translation `(scale_x, translate_x, scale_y, translate_y)` of a transform without rotation or shear.
+    pub fn axis_aligned(&self) -> Option<(f64, f64, f64, f64)> {
+        let d = &self.data;
+        if d[0][1] == 0.0 && d[1][0] == 0.0 {
+            Some((d[0][0], d[0][2], d[1][1], d[1][2]))
+        } else {
+            None
+        }
+    }
+
+    pub fn invert(&self) -> Option<Matrix2d> {
+        let d = &self.data;
+        let determinant = d[0][0] * d[1][1] - d[0][1] * d[1][0];
+        if determinant.abs() < 1e-12 {
+            return None;
+        }
+        let a = d[1][1] / determinant;
+        let b = -d[0][1] / determinant;
+        let c = -d[1][0] / determinant;
+        let e = d[0][0] / determinant;
+        Some(Matrix2d {
+            data: vec!(
+                vec!(a, b, -(a * d[0][2] + b * d[1][2])),
+                vec!(c, e, -(c * d[0][2] + e * d[1][2])),
+                vec!(0_f64, 0_f64, 1_f64)
+            )
+        })
+    }
 }
@@ -0,0 +1,118 @@
+use std::fs;
+use std::str::FromStr;
+use image::ColorType;
+use crate::render::RenderOptions;
+use crate::renki_image::RenkiImage;
+
+/// File format of the rendered frames. The integer formats hold sRGB encoded values, the float formats
+/// hold the values the frames are composited in from 0.0 to 1.0, linear light unless linear compositing is off.
+/// Transparent frames are saved with straight alpha, except EXR which holds premultiplied colors
+/// and PFM which has no alpha.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum OutputFormat {
+    #[default]
+    Png8,
+    Png16,
+    Tiff16,
+    /// 32-bit float OpenEXR.
+    Exr,
+    /// 32-bit float portable float map.
+    Pfm,
+}
+
+impl OutputFormat {
+    pub const ALL: [OutputFormat; 5] = [OutputFormat::Png8, OutputFormat::Png16, OutputFormat::Tiff16, OutputFormat::Exr, OutputFormat::Pfm];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            OutputFormat::Png8 => "png",
+            OutputFormat::Png16 => "png16",
+            OutputFormat::Tiff16 => "tiff16",
+            OutputFormat::Exr => "exr",
+            OutputFormat::Pfm => "pfm",
+        }
+    }
+
+    pub fn extension(&self) -> &'static str {
+        match self {
+            OutputFormat::Png8 | OutputFormat::Png16 => "png",
+            OutputFormat::Tiff16 => "tiff",
+            OutputFormat::Exr => "exr",
+            OutputFormat::Pfm => "pfm",
+        }
+    }
+}
+
+impl FromStr for OutputFormat {
+    type Err = String;
+
+    fn from_str(name: &str) -> Result<Self, Self::Err> {
+        OutputFormat::ALL.iter().find(|format| format.name() == name).copied()
+            .ok_or_else(|| format!("unknown output format {}", name))
+    }
+}
+
+impl RenkiImage {
+    /// Channel values of every pixel in RGB order, scaled by `scale`.
+    fn interleaved(&self, scale: f32) -> impl Iterator<Item = f32> + '_ {
+        (0..self.width * self.height).flat_map(move |index| {
+            (0..3).map(move |channel_index| self.channels[if self.channels.len() == 3 { channel_index } else { 0 }][index] * scale)
+        })
+    }
+
+    /// Adds the alpha after every `channels` values of interleaved RGB pixels.
+    fn with_alpha<T: Copy>(&self, pixels: Vec<T>, channels: usize, alpha: impl Fn(f32) -> [T; 1]) -> Vec<T> {
+        pixels.chunks(channels).zip(self.alpha.iter()).flat_map(|(pixel, value)| pixel.iter().copied().chain(alpha(*value))).collect()
+    }
+
+    fn to_rgb16(&self) -> Vec<u16> {
+        self.interleaved(65535.0 / 255.0).map(|value| value.round().clamp(0.0, 65535.0) as u16).collect()
+    }
+
+    /// Portable float map: a text header and little endian floats, rows from the bottom up.
+    fn to_pfm(&self) -> Vec<u8> {
+        let mut result = format!("PF\n{} {}\n-1.0\n", self.width, self.height).into_bytes();
+        let values: Vec<f32> = self.interleaved(1.0 / 255.0).collect();
+        for row in values.chunks(self.width * 3).rev() {
+            result.extend(row.iter().flat_map(|value| value.to_le_bytes()));
+        }
+        result
+    }
+
+    /// Saves a rendered frame in the output format of the options, `frame_index` moves the dither pattern.
+    pub fn save_frame(&self, name: &str, options: &RenderOptions, frame_index: usize) {
+        let encoded = if options.linear { Some(self.to_srgb()) } else { None };
+        let srgb = encoded.as_ref().unwrap_or(self);
+        let (width, height) = (self.width as u32, self.height as u32);
+        let transparent = options.transparent;
+        match options.output {
+            OutputFormat::Png8 if transparent => {
+                let data = srgb.with_alpha(srgb.to_rgb8(options.dither, frame_index), 3, |alpha| [(alpha * 255.0).round() as u8]);
+                image::save_buffer(name, &data, width, height, ColorType::Rgba8)
+            }
+            OutputFormat::Png8 => image::save_buffer(name, &srgb.to_rgb8(options.dither, frame_index), width, height, ColorType::Rgb8),
+            OutputFormat::Png16 | OutputFormat::Tiff16 => {
+                let (data, color_type) = if transparent {
+                    (srgb.with_alpha(srgb.to_rgb16(), 3, |alpha| [(alpha * 65535.0).round() as u16]), ColorType::Rgba16)
+                } else {
+                    (srgb.to_rgb16(), ColorType::Rgb16)
+                };
+                let data: Vec<u8> = data.iter().flat_map(|value| value.to_ne_bytes()).collect();
+                image::save_buffer(name, &data, width, height, color_type)
+            }
+            OutputFormat::Exr => {
+                let (data, color_type) = if transparent {
+                    let premultiplied: Vec<f32> = self.interleaved(1.0 / 255.0).enumerate()
+                        .map(|(index, value)| value * self.alpha[index / 3])
+                        .collect();
+                    (self.with_alpha(premultiplied, 3, |alpha| [alpha]), ColorType::Rgba32F)
+                } else {
+                    (self.interleaved(1.0 / 255.0).collect(), ColorType::Rgb32F)
+                };
+                let data: Vec<u8> = data.iter().flat_map(|value| value.to_ne_bytes()).collect();
+                image::save_buffer(name, &data, width, height, color_type)
+            }
+            OutputFormat::Pfm => fs::write(name, self.to_pfm()).map_err(image::ImageError::from),
+        }.expect("failed to write image");
+    }
+}
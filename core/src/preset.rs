@@ -0,0 +1,130 @@
+use std::collections::HashMap;
+use std::fs;
+use serde::{Deserialize, Serialize};
+
+/// Constants shaping the look of generated scenarios.
+/// Presets read from files may leave out fields, those keep the `classic` values.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct MotionPreset {
+    /// Scale relative to the settled one an image enters and leaves the frame with.
+    pub entry_scale: f64,
+    /// Angle an image enters the frame with, in radians.
+    pub entry_angle: f64,
+    /// Angle an image leaves the frame with, in radians.
+    pub exit_angle: f64,
+    /// Opacity of an image at the end of its fade in and the start of its fade out.
+    pub settle_alpha: f64,
+    /// Zoom of the zoom and drift motions between their start and end.
+    pub zoom: f64,
+    /// Extra zoom at the middle of a pan.
+    pub pan_zoom: f64,
+    /// Part of the frame kept between a pan end and the image border.
+    pub pan_margin: f64,
+    /// Zoom an image is shown with at the start of the drift and rotate motions.
+    pub drift_zoom: f64,
+    /// Angle the rotate motion starts from, in radians.
+    pub settle_angle: f64,
+}
+
+impl Default for MotionPreset {
+    fn default() -> Self {
+        MotionPreset {
+            entry_scale: 1.5,
+            entry_angle: 0.075,
+            exit_angle: -0.066,
+            settle_alpha: 0.88,
+            zoom: 1.125,
+            pan_zoom: 1.125,
+            pan_margin: 0.1,
+            drift_zoom: 1.2,
+            settle_angle: 0.05,
+        }
+    }
+}
+
+impl MotionPreset {
+    pub const NAMES: [&'static str; 4] = ["classic", "gentle", "dramatic", "documentary"];
+
+    /// Built in preset: `classic` is the default look, `gentle` keeps motion and transitions subtle,
+    /// `dramatic` exaggerates them and `documentary` crossfades straight images with slow zooms and pans.
+    pub fn named(name: &str) -> Option<MotionPreset> {
+        match name {
+            "classic" => Some(MotionPreset::default()),
+            "gentle" => Some(MotionPreset {
+                entry_scale: 1.15,
+                entry_angle: 0.02,
+                exit_angle: -0.02,
+                settle_alpha: 0.95,
+                zoom: 1.06,
+                pan_zoom: 1.04,
+                pan_margin: 0.15,
+                drift_zoom: 1.1,
+                settle_angle: 0.02,
+            }),
+            "dramatic" => Some(MotionPreset {
+                entry_scale: 2.0,
+                entry_angle: 0.15,
+                exit_angle: -0.12,
+                settle_alpha: 0.8,
+                zoom: 1.3,
+                pan_zoom: 1.25,
+                pan_margin: 0.05,
+                drift_zoom: 1.35,
+                settle_angle: 0.1,
+            }),
+            "documentary" => Some(MotionPreset {
+                entry_scale: 1.0,
+                entry_angle: 0.0,
+                exit_angle: 0.0,
+                settle_alpha: 1.0,
+                zoom: 1.1,
+                pan_zoom: 1.0,
+                pan_margin: 0.1,
+                drift_zoom: 1.15,
+                settle_angle: 0.0,
+            }),
+            _ => None,
+        }
+    }
+
+    pub fn from_json(json: &str) -> Result<MotionPreset, String> {
+        let preset: MotionPreset = serde_json::from_str(json).map_err(|error| format!("invalid preset: {}", error))?;
+        preset.validate()?;
+        Ok(preset)
+    }
+
+    /// Reads custom presets from a JSON file mapping preset names to presets.
+    pub fn load_presets(path: &str) -> Result<HashMap<String, MotionPreset>, String> {
+        let json = fs::read_to_string(path).map_err(|error| format!("failed to read presets {}: {}", path, error))?;
+        let presets: HashMap<String, MotionPreset> = serde_json::from_str(&json)
+            .map_err(|error| format!("invalid presets {}: {}", path, error))?;
+        for (name, preset) in &presets {
+            preset.validate().map_err(|error| format!("preset {}: {}", name, error))?;
+        }
+        Ok(presets)
+    }
+
+    pub fn validate(&self) -> Result<(), String> {
+        let values = [
+            self.entry_scale, self.entry_angle, self.exit_angle, self.settle_alpha, self.zoom, self.pan_zoom,
+            self.pan_margin, self.drift_zoom, self.settle_angle,
+        ];
+        if values.iter().any(|value| !value.is_finite()) {
+            return Err(String::from("preset has non-finite values"));
+        }
+        for (name, value) in [("entry scale", self.entry_scale), ("zoom", self.zoom), ("pan zoom", self.pan_zoom),
+                              ("drift zoom", self.drift_zoom)] {
+            if value <= 0.0 {
+                return Err(format!("{} {} must be positive", name, value));
+            }
+        }
+        if !(0.0..=1.0).contains(&self.settle_alpha) {
+            return Err(format!("settle alpha {} is outside 0..1", self.settle_alpha));
+        }
+        if !(0.0..0.5).contains(&self.pan_margin) {
+            return Err(format!("pan margin {} is outside 0..0.5", self.pan_margin));
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,47 @@
+use std::thread;
+use crate::dither::Dither;
+use crate::output::OutputFormat;
+use crate::resample::Resampler;
+
+/// Settings of frame rendering, kept out of the scenario so the same scenario can be rendered
+/// as a quick preview or at full quality.
+#[derive(Clone, Debug, PartialEq)]
+pub struct RenderOptions {
+    pub resampler: Resampler,
+    /// Resample downscaled images from their mipmaps, much faster on large photos.
+    pub mipmaps: bool,
+    /// Number of rendering threads, 0 uses all available cores.
+    pub threads: usize,
+    /// Render frames one by one, splitting the layers of each frame into bands of rows transformed on the threads.
+    pub tiles: bool,
+    /// Resample and composite in linear light: images are converted from sRGB when loaded and frames
+    /// are encoded back to sRGB when saved, so that edges and crossfades do not darken.
+    pub linear: bool,
+    /// Dithering of the frames quantized to 8 bits.
+    pub dither: Dither,
+    pub output: OutputFormat,
+    /// Render frames over a transparent background instead of the scenario background and save them with alpha.
+    /// Blurred image pads are still drawn.
+    pub transparent: bool,
+}
+
+impl Default for RenderOptions {
+    fn default() -> Self {
+        RenderOptions { resampler: Resampler::default(), mipmaps: true, threads: 0, tiles: false, linear: true, dither: Dither::default(), output: OutputFormat::default(), transparent: false }
+    }
+}
+
+impl RenderOptions {
+    pub fn thread_count(&self) -> usize {
+        if self.threads > 0 {
+            self.threads
+        } else {
+            thread::available_parallelism().map_or(1, |count| count.get())
+        }
+    }
+
+    /// Threads transforming each layer of a frame.
+    pub(crate) fn tile_threads(&self) -> usize {
+        if self.tiles { self.thread_count() } else { 1 }
+    }
+}
@@ -1,49 +1,63 @@
+use std::ops::Range;
 use image::{GenericImageView, ColorType};
+use crate::dither::Dither;
 use crate::matrix::Matrix2d;
 use crate::geom::{Point, Geom};
 
+/// Image with float channels from 0 to 255 and an alpha from 0 to 1. Channels hold straight,
+/// not premultiplied colors.
 #[derive(Clone)]
 pub struct RenkiImage {
     pub width: usize,
     pub height: usize,
     pub channels: Vec<Vec<f32>>,
     pub alpha: Vec<f32>,
+    /// Prefiltered downscales of the image, each half the size of the previous one.
+    pub mipmaps: Vec<RenkiImage>,
 }
 
 impl RenkiImage {
-    pub fn from_img(path: &String) -> Result<RenkiImage, String> {
-        let img = image::open(path.as_str()).map_err(|e| e.to_string())?;
+    pub fn new(width: usize, height: usize, channels: Vec<Vec<f32>>, alpha: Vec<f32>) -> RenkiImage {
+        RenkiImage { width, height, channels, alpha, mipmaps: Vec::new() }
+    }
+
+    pub fn from_img(path: &str) -> Result<RenkiImage, String> {
+        let img = image::open(path).map_err(|e| format!("Failed to load image {}: {}", path, e))?;
         let (img_width, img_height) = img.dimensions();
-        let pixels = img.to_rgb8().to_vec();
+        let pixels = img.to_rgba8().to_vec();
         let channel_size = img_width as usize * img_height as usize;
-        let mut channels = vec![Vec::with_capacity(channel_size); 3];
-        for i in 0..(pixels.len() / 3) {
-            channels[0].push(pixels[i * 3] as f32);
-            channels[1].push(pixels[i * 3 + 1] as f32);
-            channels[2].push(pixels[i * 3 + 2] as f32);
+        let mut channels: Vec<Vec<f32>> = (0..3).map(|_| Vec::with_capacity(channel_size)).collect();
+        let mut alpha = Vec::with_capacity(channel_size);
+        for i in 0..(pixels.len() / 4) {
+            channels[0].push(pixels[i * 4] as f32);
+            channels[1].push(pixels[i * 4 + 1] as f32);
+            channels[2].push(pixels[i * 4 + 2] as f32);
+            alpha.push(pixels[i * 4 + 3] as f32 / 255.0);
         }
-        Result::Ok(RenkiImage { width: img_width as usize, height: img_height as usize, channels, alpha: vec![1_f32; channel_size] })
+        Result::Ok(RenkiImage::new(img_width as usize, img_height as usize, channels, alpha))
     }
 
-    fn to_rgb8(&self) -> Vec<u8> {
-        let mut result = Vec::with_capacity(self.width as usize * self.height as usize * 3);
+    /// Channels quantized to 8 bit interleaved RGB, `frame_index` moves the dither pattern.
+    pub(crate) fn to_rgb8(&self, dither: Dither, frame_index: usize) -> Vec<u8> {
+        let quantized: Vec<Vec<u8>> = (0..3)
+            .map(|channel_index| {
+                let channel = &self.channels[if self.channels.len() == 3 { channel_index } else { 0 }];
+                dither.quantize(channel, self.width, channel_index, frame_index)
+            })
+            .collect();
+        let mut result = Vec::with_capacity(self.width * self.height * 3);
         for i in 0..self.channels[0].len() {
-            let r = self.channels[0][i];
-            let g = if self.channels.len() == 3 { self.channels[1][i] } else { self.channels[0][i] };
-            let b = if self.channels.len() == 3 { self.channels[2][i] } else { self.channels[0][i] };
-            result.push(if r < 0_f32 { 0_u8 } else if r > 255_f32 { 255_u8 } else { r as u8 });
-            result.push(if g < 0_f32 { 0_u8 } else if g > 255_f32 { 255_u8 } else { g as u8 });
-            result.push(if b < 0_f32 { 0_u8 } else if b > 255_f32 { 255_u8 } else { b as u8 });
+            result.extend(quantized.iter().map(|channel| channel[i]));
         }
         result
     }
 
     pub fn save(self: &RenkiImage, name: &str) {
-        image::save_buffer(name, &self.to_rgb8(), self.width as u32, self.height as u32, ColorType::Rgb8)
+        image::save_buffer(name, &self.to_rgb8(Dither::None, 0), self.width as u32, self.height as u32, ColorType::Rgb8)
             .expect("failed to write image");
     }
 
-    fn calc_area_in_pixel(points: &Vec<Point>, pixel_x: i32, pixel_y: i32) -> f64 {
+    fn calc_area_in_pixel(points: &[Point], pixel_x: i32, pixel_y: i32) -> f64 {
         let mut area = 0.0;
         let mut triangle = vec![Point::zero(); 3];
         triangle[0] = points[0].translate(-pixel_x as f64, -pixel_y as f64);
@@ -58,17 +72,59 @@ impl RenkiImage {
         area
     }
 
+    /// Source pixels that can cover a part of the `rows` of a `width` pixels wide frame: the bounding box of the rows
+    /// mapped back to the image, grown by a pixel and clipped to the image. `None` if no pixel can.
+    fn visible_region(&self, matrix: &Matrix2d, width: usize, rows: &Range<usize>) -> Option<(Range<usize>, Range<usize>)> {
+        let inverse = matrix.invert()?;
+        let (top, bottom) = (rows.start as f64, rows.end as f64);
+        let corners = [(0.0, top), (width as f64, top), (0.0, bottom), (width as f64, bottom)]
+            .map(|(x, y)| inverse.apply(&Point::new(x, y)));
+        let x_min = corners.iter().map(|p| p.x).fold(f64::INFINITY, f64::min).floor() - 1.0;
+        let x_max = corners.iter().map(|p| p.x).fold(f64::NEG_INFINITY, f64::max).ceil() + 1.0;
+        let y_min = corners.iter().map(|p| p.y).fold(f64::INFINITY, f64::min).floor() - 1.0;
+        let y_max = corners.iter().map(|p| p.y).fold(f64::NEG_INFINITY, f64::max).ceil() + 1.0;
+        let columns = x_min.max(0.0) as usize..x_max.clamp(0.0, self.width as f64) as usize;
+        let rows = y_min.max(0.0) as usize..y_max.clamp(0.0, self.height as f64) as usize;
+        if columns.is_empty() || rows.is_empty() {
+            None
+        } else {
+            Some((columns, rows))
+        }
+    }
+
+    /// Transforms the image to a `width` x `height` frame. Every source pixel is splatted over the frame pixels
+    /// it covers weighted by its alpha, only the pixels inside the frame are processed and nothing is drawn
+    /// when `alpha` is 0. The frame alpha is the covered area times the source and the layer `alpha`.
     pub fn transform(&self, matrix: &Matrix2d, width: usize, height: usize, alpha: f64) -> RenkiImage {
-        let channel_size = width * height;
-        let mut data = Vec::new();
-        for _channel_index in 0..self.channels.len() {
-            data.push(vec![0_f32; channel_size]);
+        let (data, alpha_data) = self.transform_rows(matrix, width, 0..height, alpha);
+        RenkiImage::new(width, height, data, alpha_data)
+    }
+
+    /// Area transform of the frame `rows` only, see [`RenkiImage::transform`]. Every frame pixel sums the source
+    /// pixels in the same order whatever the rows, so frames split into bands of rows are the same.
+    pub(crate) fn transform_rows(&self, matrix: &Matrix2d, width: usize, rows: Range<usize>, alpha: f64) -> (Vec<Vec<f32>>, Vec<f32>) {
+        let size = width * rows.len();
+        let mut data = vec![vec![0_f32; size]; self.channels.len()];
+        let mut alpha_data = vec![0_f32; size];
+        if alpha <= 0.0 {
+            return (data, alpha_data);
+        }
+        let (source_columns, source_rows) = match self.visible_region(matrix, width, &rows) {
+            Some(region) => region,
+            None => return (data, alpha_data),
+        };
+
+        if let Some((scale_x, translate_x, scale_y, translate_y)) = matrix.axis_aligned() {
+            self.transform_axis_aligned(&mut data, &mut alpha_data, (width, rows), (source_columns, source_rows),
+                                        (scale_x, translate_x, scale_y, translate_y));
+            RenkiImage::unpremultiply(&mut data, &mut alpha_data, alpha);
+            return (data, alpha_data);
         }
-        let mut alpha_data = vec![0_f32; channel_size];
 
+        let (top, bottom) = (rows.start as i32, rows.end as i32);
         let mut transformed_pixel = vec![Point::zero(); 4];
-        for y in 0..self.height {
-            for x in 0..self.width {
+        for y in source_rows {
+            for x in source_columns.clone() {
                 let source_index = y * self.width + x;
                 transformed_pixel[0] = matrix.apply(&Point::new(x as f64, y as f64));
                 transformed_pixel[1] = matrix.apply(&Point::new(x as f64, y as f64 + 1.0));
@@ -79,37 +135,156 @@ impl RenkiImage {
                 let y_min = transformed_pixel.iter().map(|p| p.y as i32).min().unwrap();
                 let y_max = transformed_pixel.iter().map(|p| p.y as i32).max().unwrap();
                 for y_dest in y_min..=y_max {
-                    if y_dest >= 0 && y_dest < height as i32 {
+                    if y_dest >= top && y_dest < bottom {
                         for x_dest in x_min..=x_max {
                             if x_dest >= 0 && x_dest < width as i32 {
-                                let dest_index = y_dest as usize * width + x_dest as usize;
-                                let area = RenkiImage::calc_area_in_pixel(&transformed_pixel, x_dest, y_dest);
-                                for channel_index in 0..data.len() {
-                                    let v = self.channels[channel_index][source_index];
-                                    data[channel_index][dest_index] += v * area as f32;
+                                let dest_index = (y_dest - top) as usize * width + x_dest as usize;
+                                let weight = RenkiImage::calc_area_in_pixel(&transformed_pixel, x_dest, y_dest) as f32
+                                    * self.alpha[source_index];
+                                for (channel, source) in data.iter_mut().zip(self.channels.iter()) {
+                                    channel[dest_index] += source[source_index] * weight;
                                 }
-                                alpha_data[dest_index] += (area * alpha) as f32;
+                                alpha_data[dest_index] += weight;
                             }
                         }
                     }
                 }
             }
         }
-        RenkiImage { width, height, channels: data, alpha: alpha_data }
+        RenkiImage::unpremultiply(&mut data, &mut alpha_data, alpha);
+        (data, alpha_data)
+    }
+
+    /// Divides channels accumulated premultiplied by their weights in `alpha_data`, which becomes the alpha
+    /// of the layer drawn with opacity `alpha`.
+    pub(crate) fn unpremultiply(data: &mut [Vec<f32>], alpha_data: &mut [f32], alpha: f64) {
+        for (index, weight) in alpha_data.iter_mut().enumerate() {
+            if *weight > 1e-6 {
+                data.iter_mut().for_each(|channel| channel[index] /= *weight);
+            }
+            *weight = (*weight * alpha as f32).clamp(0.0, 1.0);
+        }
+    }
+
+    /// Frame pixels of `dest` covered by the source pixels of `source` along one axis, for every frame pixel
+    /// of `dest` the source pixels and the lengths they cover.
+    fn axis_coverage(source: Range<usize>, scale: f64, translate: f64, dest: &Range<usize>) -> Vec<Vec<(usize, f32)>> {
+        let mut coverage = vec![Vec::new(); dest.len()];
+        for source_index in source {
+            let start = source_index as f64 * scale + translate;
+            let end = start + scale;
+            let (low, high) = (start.min(end), start.max(end));
+            let first = low.floor().max(dest.start as f64) as usize;
+            let last = high.ceil().clamp(dest.start as f64, dest.end as f64) as usize;
+            for dest_index in first..last {
+                let length = high.min(dest_index as f64 + 1.0) - low.max(dest_index as f64);
+                if length > 0.0 {
+                    coverage[dest_index - dest.start].push((source_index, length as f32));
+                }
+            }
+        }
+        coverage
+    }
+
+    /// Area transform of a matrix without rotation: source pixels map to rectangles, so the covered area
+    /// is the product of the covered lengths along both axes and the image is resampled one axis at a time.
+    /// Accumulates the frame `rows` premultiplied by the source alpha and the weights like [`RenkiImage::transform_rows`].
+    fn transform_axis_aligned(&self, data: &mut [Vec<f32>], alpha_data: &mut [f32], (width, rows): (usize, Range<usize>),
+                              (columns, source_rows): (Range<usize>, Range<usize>),
+                              (scale_x, translate_x, scale_y, translate_y): (f64, f64, f64, f64)) {
+        let first_row = source_rows.start;
+        let coverage_x = RenkiImage::axis_coverage(columns, scale_x, translate_x, &(0..width));
+        let coverage_y = RenkiImage::axis_coverage(source_rows.clone(), scale_y, translate_y, &rows);
+        let mut resampled_rows = vec![0_f32; source_rows.len() * width];
+        let mut resample = |plane: &mut [f32], source: &dyn Fn(usize) -> f32| {
+            for (row, y) in source_rows.clone().enumerate() {
+                for (value, taps) in resampled_rows[row * width..(row + 1) * width].iter_mut().zip(coverage_x.iter()) {
+                    *value = taps.iter().map(|(x, length)| source(y * self.width + x) * length).sum();
+                }
+            }
+            for (y_dest, taps) in coverage_y.iter().enumerate() {
+                let dest_row = &mut plane[y_dest * width..(y_dest + 1) * width];
+                for (y, length) in taps {
+                    let row = &resampled_rows[(y - first_row) * width..(y - first_row + 1) * width];
+                    dest_row.iter_mut().zip(row).for_each(|(dest, value)| *dest += value * length);
+                }
+            }
+        };
+        for (channel, source) in data.iter_mut().zip(self.channels.iter()) {
+            resample(channel, &|index| source[index] * self.alpha[index]);
+        }
+        resample(alpha_data, &|index| self.alpha[index]);
     }
 
     pub fn blend(&self, image: &RenkiImage) -> RenkiImage {
         let channel_size = self.width * self.height;
+        let alpha_data: Vec<f32> = (0..channel_size).map(|i| image.alpha[i] + self.alpha[i] * (1_f32 - image.alpha[i])).collect();
         let mut data = Vec::new();
         for channel_index in 0..self.channels.len() {
             let mut channel_data = Vec::with_capacity(channel_size);
-            for i in 0..channel_size {
+            for (i, blended_alpha) in alpha_data.iter().enumerate() {
                 let alpha = image.alpha[i];
-                let v = self.channels[channel_index][i] * (1_f32 - alpha) + image.channels[channel_index][i] * alpha;
+                let v = if self.alpha[i] == 1_f32 {
+                    self.channels[channel_index][i] * (1_f32 - alpha) + image.channels[channel_index][i] * alpha
+                } else if *blended_alpha > 0_f32 {
+                    (self.channels[channel_index][i] * self.alpha[i] * (1_f32 - alpha) + image.channels[channel_index][i] * alpha)
+                        / blended_alpha
+                } else {
+                    0_f32
+                };
                 channel_data.push(v);
             }
             data.push(channel_data);
         }
-        RenkiImage { width: self.width, height: self.height, channels: data, alpha: vec![1_f32; channel_size] }
+        RenkiImage::new(self.width, self.height, data, alpha_data)
     }
-}
\ No newline at end of file
+
+    /// Half size copy of the image, every pixel averages a 2x2 block weighted by alpha. On odd sizes the last pixels
+    /// average the part of their block inside the image.
+    pub(crate) fn half_size(&self) -> RenkiImage {
+        let (width, height) = (self.width.div_ceil(2), self.height.div_ceil(2));
+        let downscale = |value: &dyn Fn(usize) -> f32| {
+            let mut result = Vec::with_capacity(width * height);
+            for y in 0..height {
+                let rows = [y * 2, (y * 2 + 1).min(self.height - 1)];
+                for x in 0..width {
+                    let columns = [x * 2, (x * 2 + 1).min(self.width - 1)];
+                    let sum: f32 = rows.iter().flat_map(|row| columns.iter().map(move |column| value(row * self.width + column))).sum();
+                    result.push(sum * 0.25);
+                }
+            }
+            result
+        };
+        let mut alpha = downscale(&|index| self.alpha[index]);
+        let mut channels: Vec<Vec<f32>> = self.channels.iter()
+            .map(|channel| downscale(&|index| channel[index] * self.alpha[index]))
+            .collect();
+        RenkiImage::unpremultiply(&mut channels, &mut alpha, 1.0);
+        RenkiImage::new(width, height, channels, alpha)
+    }
+
+    /// Builds the mipmaps down to 16 pixels on the shorter side.
+    pub fn build_mipmaps(&mut self) {
+        let mut mipmaps: Vec<RenkiImage> = Vec::new();
+        while mipmaps.last().map_or(self.width.min(self.height), |level| level.width.min(level.height)) >= 32 {
+            let level = mipmaps.last().unwrap_or(self).half_size();
+            mipmaps.push(level);
+        }
+        self.mipmaps = mipmaps;
+    }
+
+    /// Largest mipmap the `matrix` still scales down or keeps at its size, and the matrix transforming it
+    /// like `matrix` transforms the image.
+    pub fn mipmap_level(&self, matrix: Matrix2d) -> (&RenkiImage, Matrix2d) {
+        let scale = matrix.average_scale();
+        if scale <= 0.0 || !scale.is_finite() {
+            return (self, matrix);
+        }
+        let level = ((1.0 / scale).log2() + 1e-9).floor().clamp(0.0, self.mipmaps.len() as f64) as usize;
+        if level == 0 {
+            return (self, matrix);
+        }
+        let level_matrix = Matrix2d::scale((1_u32 << level) as f64).multiply(&matrix);
+        (&self.mipmaps[level - 1], level_matrix)
+    }
+}
@@ -0,0 +1,195 @@
+use std::f64::consts::PI;
+use std::ops::Range;
+use std::str::FromStr;
+use std::thread;
+use crate::geom::Point;
+use crate::matrix::Matrix2d;
+use crate::renki_image::RenkiImage;
+
+/// Filter used to resample images into frames.
+/// `area` splats every source pixel over the frame pixels it covers, exact box filtering.
+/// The other filters map every frame pixel back to the source image and sample it, widening
+/// the filter on downscales so that they do not alias.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Resampler {
+    #[default]
+    Area,
+    Nearest,
+    Bilinear,
+    /// Catmull-Rom cubic.
+    Bicubic,
+    Lanczos3,
+}
+
+impl Resampler {
+    pub const ALL: [Resampler; 5] = [Resampler::Area, Resampler::Nearest, Resampler::Bilinear, Resampler::Bicubic, Resampler::Lanczos3];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Resampler::Area => "area",
+            Resampler::Nearest => "nearest",
+            Resampler::Bilinear => "bilinear",
+            Resampler::Bicubic => "bicubic",
+            Resampler::Lanczos3 => "lanczos3",
+        }
+    }
+
+    /// Filter radius in source pixels at unit scale.
+    fn support(&self) -> f64 {
+        match self {
+            Resampler::Area | Resampler::Nearest => 0.5,
+            Resampler::Bilinear => 1.0,
+            Resampler::Bicubic => 2.0,
+            Resampler::Lanczos3 => 3.0,
+        }
+    }
+
+    /// Weight of a source pixel at the signed distance `distance`. The box of `nearest` is half open,
+    /// so a pixel center on a source pixel boundary takes exactly one of the two pixels.
+    fn weight(&self, distance: f64) -> f64 {
+        let x = distance.abs();
+        match self {
+            Resampler::Area | Resampler::Nearest => if (-0.5..0.5).contains(&distance) { 1.0 } else { 0.0 },
+            Resampler::Bilinear => (1.0 - x).max(0.0),
+            Resampler::Bicubic => {
+                if x < 1.0 {
+                    1.5 * x * x * x - 2.5 * x * x + 1.0
+                } else if x < 2.0 {
+                    -0.5 * x * x * x + 2.5 * x * x - 4.0 * x + 2.0
+                } else {
+                    0.0
+                }
+            }
+            Resampler::Lanczos3 => {
+                if x < 1e-9 {
+                    1.0
+                } else if x < 3.0 {
+                    let px = PI * x;
+                    3.0 * px.sin() * (px / 3.0).sin() / (px * px)
+                } else {
+                    0.0
+                }
+            }
+        }
+    }
+
+    /// Taps of the filter centered at `center` and stretched by `footprint`: the first source pixel and the weights.
+    fn taps(&self, center: f64, footprint: f64, weights: &mut Vec<f64>) -> isize {
+        let radius = self.support() * footprint;
+        let first = (center - radius).ceil() as isize;
+        let last = (center + radius).floor() as isize;
+        weights.clear();
+        weights.extend((first..=last).map(|index| self.weight((index as f64 - center) / footprint)));
+        first
+    }
+}
+
+impl FromStr for Resampler {
+    type Err = String;
+
+    fn from_str(name: &str) -> Result<Self, Self::Err> {
+        Resampler::ALL.iter().find(|resampler| resampler.name() == name).copied()
+            .ok_or_else(|| format!("unknown resampler {}", name))
+    }
+}
+
+impl RenkiImage {
+    /// Transform with the given resampler, see [`RenkiImage::transform`].
+    pub fn transform_with(&self, matrix: &Matrix2d, width: usize, height: usize, alpha: f64, resampler: Resampler) -> RenkiImage {
+        self.transform_threaded(matrix, width, height, alpha, resampler, 1)
+    }
+
+    /// Transform with the given resampler, the frame is split into bands of rows rendered on up to `threads` threads.
+    /// The result does not depend on the number of threads.
+    pub fn transform_threaded(&self, matrix: &Matrix2d, width: usize, height: usize, alpha: f64, resampler: Resampler,
+                              threads: usize) -> RenkiImage {
+        let channel_size = width * height;
+        let inverse = match matrix.invert() {
+            Some(inverse) if alpha > 0.0 => inverse,
+            _ => return RenkiImage::new(width, height, vec![vec![0_f32; channel_size]; self.channels.len()], vec![0_f32; channel_size]),
+        };
+        let band = |rows: Range<usize>| match resampler {
+            Resampler::Area => self.transform_rows(matrix, width, rows, alpha),
+            _ => self.resample_rows(&inverse, width, rows, alpha, resampler),
+        };
+        let threads = threads.clamp(1, height.max(1));
+        let band_height = height.div_ceil(threads);
+        let bands: Vec<(Vec<Vec<f32>>, Vec<f32>)> = if threads == 1 {
+            vec![band(0..height)]
+        } else {
+            thread::scope(|scope| {
+                let workers: Vec<_> = (0..height).step_by(band_height)
+                    .map(|top| {
+                        let band = &band;
+                        scope.spawn(move || band(top..(top + band_height).min(height)))
+                    })
+                    .collect();
+                workers.into_iter().map(|worker| worker.join().expect("Resampling thread failed")).collect()
+            })
+        };
+        let mut data: Vec<Vec<f32>> = (0..self.channels.len()).map(|_| Vec::with_capacity(channel_size)).collect();
+        let mut alpha_data = Vec::with_capacity(channel_size);
+        for (band_channels, band_alpha) in bands {
+            for (channel, band_channel) in data.iter_mut().zip(band_channels) {
+                channel.extend(band_channel);
+            }
+            alpha_data.extend(band_alpha);
+        }
+        RenkiImage::new(width, height, data, alpha_data)
+    }
+
+    /// Inverse mapping resampling of the frame `rows`: every frame pixel center is mapped back to the source image
+    /// and filtered there. Like the area transform, the channels are filtered weighted by the source alpha and
+    /// the alpha is the filtered source alpha, zero outside of the image, times `alpha`.
+    fn resample_rows(&self, inverse: &Matrix2d, width: usize, rows: Range<usize>, alpha: f64,
+                     resampler: Resampler) -> (Vec<Vec<f32>>, Vec<f32>) {
+        let size = width * rows.len();
+        let mut data = vec![vec![0_f32; size]; self.channels.len()];
+        let mut alpha_data = vec![0_f32; size];
+        // source pixels per frame pixel, the filter is widened by it on downscales
+        let footprint = inverse.average_scale().max(1.0);
+
+        let (mut weights_x, mut weights_y) = (Vec::new(), Vec::new());
+        let mut sums = vec![0_f64; self.channels.len()];
+        for (row, y) in rows.enumerate() {
+            for x in 0..width {
+                let source = inverse.apply(&Point::new(x as f64 + 0.5, y as f64 + 0.5));
+                let first_x = resampler.taps(source.x - 0.5, footprint, &mut weights_x);
+                let first_y = resampler.taps(source.y - 0.5, footprint, &mut weights_y);
+                let total: f64 = weights_x.iter().sum::<f64>() * weights_y.iter().sum::<f64>();
+                if total.abs() < 1e-12 {
+                    continue;
+                }
+                sums.iter_mut().for_each(|sum| *sum = 0.0);
+                let mut weights = 0.0;
+                for (j, weight_y) in weights_y.iter().enumerate() {
+                    let source_y = first_y + j as isize;
+                    if source_y < 0 || source_y >= self.height as isize {
+                        continue;
+                    }
+                    for (i, weight_x) in weights_x.iter().enumerate() {
+                        let source_x = first_x + i as isize;
+                        if source_x < 0 || source_x >= self.width as isize {
+                            continue;
+                        }
+                        let source_index = source_y as usize * self.width + source_x as usize;
+                        let weight = weight_x * weight_y * self.alpha[source_index] as f64;
+                        for (sum, channel) in sums.iter_mut().zip(self.channels.iter()) {
+                            *sum += channel[source_index] as f64 * weight;
+                        }
+                        weights += weight;
+                    }
+                }
+                if weights.abs() < 1e-12 {
+                    continue;
+                }
+                let dest_index = row * width + x;
+                for (channel, sum) in data.iter_mut().zip(sums.iter()) {
+                    channel[dest_index] = (sum / weights) as f32;
+                }
+                alpha_data[dest_index] = ((weights / total).clamp(0.0, 1.0) * alpha) as f32;
+            }
+        }
+        (data, alpha_data)
+    }
+}
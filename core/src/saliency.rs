@@ -0,0 +1,139 @@
+//! Estimation of the interesting region of an image without focus metadata.
+//!
+//! The image is split into a grid of square cells. Each cell gets three measures computed from the image channels:
+//! edge energy (mean luminance gradient), contrast (luminance standard deviation) and color saliency
+//! (distance of the cell mean color from the image mean color). The measures are normalized, averaged
+//! and weighted slightly towards the image center. The salient region covers the cells scoring
+//! above half of the best one.
+
+use crate::renki_image::RenkiImage;
+use crate::focus::FocusRegion;
+
+/// Cells along the longer image side.
+const GRID_SIZE: usize = 48;
+/// Pixels sampled along each side of a cell.
+const CELL_SAMPLES: usize = 8;
+/// Images whose measures all stay below this, in 0..255 channel units, are flat and have no salient region.
+const FLAT_THRESHOLD: f64 = 1.0;
+
+#[derive(Clone, Default)]
+struct Cell {
+    samples: f64,
+    color: [f64; 3],
+    luma: f64,
+    luma_squared: f64,
+    gradient: f64,
+}
+
+fn luma(color: [f64; 3]) -> f64 {
+    0.299 * color[0] + 0.587 * color[1] + 0.114 * color[2]
+}
+
+fn pixel(image: &RenkiImage, x: usize, y: usize) -> [f64; 3] {
+    let index = y * image.width + x;
+    let channel = |channel_index: usize| image.channels[channel_index.min(image.channels.len() - 1)][index] as f64;
+    [channel(0), channel(1), channel(2)]
+}
+
+fn normalized(values: Vec<f64>) -> Option<Vec<f64>> {
+    let max = values.iter().copied().fold(0.0, f64::max);
+    if max < FLAT_THRESHOLD {
+        None
+    } else {
+        Some(values.iter().map(|value| value / max).collect())
+    }
+}
+
+/// Salient region of the image, `None` for flat images.
+pub fn salient_region(image: &RenkiImage) -> Option<FocusRegion> {
+    if image.width < 2 || image.height < 2 || image.channels.is_empty() {
+        return None;
+    }
+    let cell_size = (image.width.max(image.height) / GRID_SIZE).max(1);
+    let step = (cell_size / CELL_SAMPLES).max(1);
+    let columns = image.width.div_ceil(cell_size);
+    let rows = image.height.div_ceil(cell_size);
+    let mut cells = vec![Cell::default(); columns * rows];
+    for y in (0..image.height - 1).step_by(step) {
+        for x in (0..image.width - 1).step_by(step) {
+            let color = pixel(image, x, y);
+            let value = luma(color);
+            let gradient = (luma(pixel(image, x + 1, y)) - value).abs() + (luma(pixel(image, x, y + 1)) - value).abs();
+            let cell = &mut cells[(y / cell_size) * columns + x / cell_size];
+            cell.samples += 1.0;
+            for (sum, value) in cell.color.iter_mut().zip(color) {
+                *sum += value;
+            }
+            cell.luma += value;
+            cell.luma_squared += value * value;
+            cell.gradient += gradient;
+        }
+    }
+
+    let sampled: Vec<&Cell> = cells.iter().filter(|cell| cell.samples > 0.0).collect();
+    let samples: f64 = sampled.iter().map(|cell| cell.samples).sum();
+    let mut mean_color = [0.0; 3];
+    for cell in &sampled {
+        for (mean, sum) in mean_color.iter_mut().zip(cell.color) {
+            *mean += sum / samples;
+        }
+    }
+    let cell_mean = |cell: &Cell, sum: f64| if cell.samples > 0.0 { sum / cell.samples } else { 0.0 };
+    let edges: Vec<f64> = cells.iter().map(|cell| cell_mean(cell, cell.gradient)).collect();
+    let contrasts: Vec<f64> = cells.iter()
+        .map(|cell| (cell_mean(cell, cell.luma_squared) - cell_mean(cell, cell.luma).powi(2)).max(0.0).sqrt())
+        .collect();
+    let colors: Vec<f64> = cells.iter()
+        .map(|cell| {
+            if cell.samples == 0.0 {
+                return 0.0;
+            }
+            (0..3).map(|channel_index| (cell.color[channel_index] / cell.samples - mean_color[channel_index]).powi(2))
+                .sum::<f64>().sqrt()
+        })
+        .collect();
+    let maps: Vec<Vec<f64>> = [edges, contrasts, colors].into_iter().filter_map(normalized).collect();
+    if maps.is_empty() {
+        return None;
+    }
+
+    let cell_center = |index: usize| {
+        let x = ((index % columns) as f64 + 0.5) * cell_size as f64;
+        let y = ((index / columns) as f64 + 0.5) * cell_size as f64;
+        (x.min(image.width as f64), y.min(image.height as f64))
+    };
+    let saliency: Vec<f64> = (0..cells.len())
+        .map(|index| {
+            let (x, y) = cell_center(index);
+            let dx = x / image.width as f64 * 2.0 - 1.0;
+            let dy = y / image.height as f64 * 2.0 - 1.0;
+            let center_bias = 1.0 - 0.125 * (dx * dx + dy * dy);
+            maps.iter().map(|map| map[index]).sum::<f64>() / maps.len() as f64 * center_bias
+        })
+        .collect();
+
+    let threshold = saliency.iter().copied().fold(0.0, f64::max) * 0.5;
+    let (mut weights, mut sum_x, mut sum_y, mut sum_xx, mut sum_yy) = (0.0, 0.0, 0.0, 0.0, 0.0);
+    for (index, value) in saliency.iter().enumerate() {
+        let weight = value - threshold;
+        if weight > 0.0 {
+            let (x, y) = cell_center(index);
+            weights += weight;
+            sum_x += weight * x;
+            sum_y += weight * y;
+            sum_xx += weight * x * x;
+            sum_yy += weight * y * y;
+        }
+    }
+    if weights == 0.0 {
+        return None;
+    }
+    let (center_x, center_y) = (sum_x / weights, sum_y / weights);
+    let half_width = ((sum_xx / weights - center_x * center_x).max(0.0).sqrt() * 1.5).max(cell_size as f64 * 0.5);
+    let half_height = ((sum_yy / weights - center_y * center_y).max(0.0).sqrt() * 1.5).max(cell_size as f64 * 0.5);
+    let left = (center_x - half_width).max(0.0);
+    let top = (center_y - half_height).max(0.0);
+    let right = (center_x + half_width).min(image.width as f64);
+    let bottom = (center_y + half_height).min(image.height as f64);
+    Some(FocusRegion { x: left, y: top, width: right - left, height: bottom - top })
+}
@@ -1,160 +1,635 @@
+//! Scenario description and frame rendering.
+//!
+//! A scenario can be generated with [`Scenario::generate_scenario`] or loaded from a JSON file
+//! with [`Scenario::load`]. The file format mirrors the structs below:
+//!
+//! ```json
+//! {
+//!   "version": 3,
+//!   "width": 1920,
+//!   "height": 1080,
+//!   "fps": 25,
+//!   "duration": 10.0,
+//!   "images": [
+//!     {
+//!       "image": "photos/first.jpg",
+//!       "tracks": {
+//!         "anchor_x": {"keys": [{"time": 0.0, "value": 800.0}, {"time": 6.0, "value": 1600.0, "easing": "ease_out"}]},
+//!         "anchor_y": {"keys": [{"time": 0.0, "value": 600.0}]},
+//!         "offset_x": {"keys": [{"time": 0.0, "value": 960.0}]},
+//!         "offset_y": {"keys": [{"time": 0.0, "value": 540.0}]},
+//!         "scale": {"interpolation": "catmull_rom", "keys": [{"time": 0.0, "value": 1.5}, {"time": 6.0, "value": 1.0}]},
+//!         "angle": {"keys": [{"time": 0.0, "value": 0.075}, {"time": 2.0, "value": 0.0}]},
+//!         "alpha": {"keys": [{"time": 0.0, "value": 0.0}, {"time": 1.0, "value": 1.0}, {"time": 5.0, "value": 1.0},
+//!                            {"time": 6.0, "value": 0.0}]}
+//!       }
+//!     }
+//!   ]
+//! }
+//! ```
+//!
+//! * `version` - format version, files without it are read as version 1.
+//! * `width`, `height` - output frame size in pixels.
+//! * `fps` - frame rate, e.g. 24, 25, 30, 60 or 29.97, `duration` - scenario length in seconds.
+//!   Frame `n` shows the scenario at `n / fps` seconds.
+//! * `image` - path to the image file; relative paths are resolved against the scenario file directory.
+//! * `tracks` - independent keyframe tracks of the image properties:
+//!   * `anchor_x`, `anchor_y` - point of the source image (in source pixels) placed at the offset point.
+//!   * `offset_x`, `offset_y` - point of the output frame (in frame pixels) where the anchor is placed.
+//!   * `scale` - image scale around the anchor, `angle` - rotation around the anchor in radians.
+//!   * `alpha` - image opacity from 0.0 to 1.0, optional, defaults to 1.0. `angle` defaults to 0.0.
+//!
+//!   The image is shown from the first to the last keyframe over all of its tracks,
+//!   a track holds its first and last values outside of its own keyframes.
+//! * `interpolation` - curve type of a track, one of `linear`, `catmull_rom`,
+//!   `monotone_cubic` (default, never overshoots the keyframes) or `bezier`.
+//! * `space` - optional space the track is interpolated in: `linear` (default), `logarithmic`
+//!   (uniform zoom speed for `scale`) or `shortest_arc` (`angle` turns the shorter way between keyframes).
+//! * `time` - keyframe time in seconds from the scenario start.
+//!   Keyframe times of a track must be strictly increasing.
+//! * `tangent` - optional keyframe tangent for `bezier` tracks, e.g. `{"incoming": 0.0, "outgoing": -2.0}`,
+//!   in value units per second. Keyframes without tangents get Catmull-Rom ones.
+//! * `easing` - optional easing of the segment from this keyframe to the next one: `linear` (default), `ease_in`,
+//!   `ease_out`, `ease_in_out`, `step` (hold the keyframe until the next one) or
+//!   `{"cubic_bezier": {"x1": 0.25, "y1": 0.1, "x2": 0.25, "y2": 1.0}}`.
+//! * `transition` - optional transition from the previous image, run while the two images overlap, e.g.
+//!   `{"type": "wipe", "direction": "left", "easing": "ease_in_out", "softness": 0.05}`. Types are `crossfade`,
+//!   `dip` (with `color`), `wipe` and `slide` (with `direction` and `push`), `iris`, `zoom_through` and
+//!   `luma_matte` (with a grayscale `image`). Without a transition the images crossfade along their alpha tracks.
+//! * `background` - optional fill of the frame areas no image covers, black by default:
+//!   `{"type": "solid", "color": [r, g, b]}`, `{"type": "gradient", "from": [r, g, b], "to": [r, g, b], "angle": 1.57}`,
+//!   `{"type": "blur", "radius": 40.0, "brightness": 0.7}` (a blurred copy of each image scaled up to cover the frame)
+//!   or `{"type": "image", "image": "background.jpg"}`. Colors are from 0 to 255.
+//! * `focus` - optional region of the image kept in the frame by the generator, `{"x", "y", "width", "height"}`
+//!   in source pixels. It is not used for rendering, [`Scenario::focus_regions`] reads it back for regenerating.
+//!
+//! Files before version 3 have `length` (number of frames) instead of `fps` and `duration`, and keyframe times
+//! normalized so that the whole scenario runs from 0.0 to 1.0. They are read as 25 fps with `length / 25` seconds.
+//!
+//! Version 1 files describe each image with `points` instead of `tracks`, every point keys all properties
+//! at once: `{"time": 0.0, "anchor_x": 800.0, "anchor_y": 600.0, "offset_x": 960.0, "offset_y": 540.0,
+//! "scale": 1.5, "angle": 0.075, "alpha": 0.0}` with optional `tangents` per property and `easing`.
+//! Their curve types are set once for the scenario with `"interpolation": {"scale": "catmull_rom"}`
+//! per property group (`anchor`, `offset`, `scale`, `angle`, `alpha`); their scale is interpolated
+//! in logarithmic space and angle along the shortest arc. Such files are still accepted.
+//!
+//! Images are drawn in the order they are listed, later images on top.
+
 use std::collections::HashMap;
+use std::fs;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::mpsc;
+use std::thread;
+use crate::color::color_to_linear;
+use serde::{Deserialize, Serialize};
 use crate::renki_image::RenkiImage;
 use crate::matrix::Matrix2d;
+use crate::interpolation::{Easing, Interpolation, Keyframe, Tangent};
+use crate::track::{Track, ValueSpace};
+use crate::frame_rate::FrameRate;
+use crate::focus::FocusRegion;
+use crate::transition::{Transition, TransitionKind};
+use crate::background::{Background, BackgroundLayers};
+use crate::render::RenderOptions;
+
+pub const SCENARIO_VERSION: u32 = 3;
 
-#[derive(Clone, Debug)]
+fn default_version() -> u32 {
+    1
+}
+
+fn default_angle() -> Track {
+    Track::constant(0.0)
+}
+
+fn default_alpha() -> Track {
+    Track::constant(1.0)
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
 pub struct ScenarioPoint {
-    time: f64,
-    anchor_x: f64,
-    anchor_y: f64,
-    offset_x: f64,
-    offset_y: f64,
-    scale: f64,
-    angle: f64,
-    alpha: f64,
+    pub time: f64,
+    pub anchor_x: f64,
+    pub anchor_y: f64,
+    pub offset_x: f64,
+    pub offset_y: f64,
+    pub scale: f64,
+    pub angle: f64,
+    pub alpha: f64,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tangents: Option<ScenarioTangents>,
+    #[serde(default, skip_serializing_if = "Easing::is_linear")]
+    pub easing: Easing,
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ScenarioTangents {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub anchor_x: Option<Tangent>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub anchor_y: Option<Tangent>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub offset_x: Option<Tangent>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub offset_y: Option<Tangent>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub scale: Option<Tangent>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub angle: Option<Tangent>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub alpha: Option<Tangent>,
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct InterpolationSettings {
+    pub anchor: Interpolation,
+    pub offset: Interpolation,
+    pub scale: Interpolation,
+    pub angle: Interpolation,
+    pub alpha: Interpolation,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct ImageTracks {
+    pub anchor_x: Track,
+    pub anchor_y: Track,
+    pub offset_x: Track,
+    pub offset_y: Track,
+    pub scale: Track,
+    #[serde(default = "default_angle")]
+    pub angle: Track,
+    #[serde(default = "default_alpha")]
+    pub alpha: Track,
 }
 
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
 pub struct ImageScenario {
-    image: String,
-    points: Vec<ScenarioPoint>,
+    pub image: String,
+    pub tracks: ImageTracks,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub focus: Option<FocusRegion>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub transition: Option<Transition>,
 }
 
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(try_from = "ScenarioFile")]
 pub struct Scenario {
-    images: Vec<ImageScenario>,
+    pub version: u32,
+    pub width: usize,
+    pub height: usize,
+    pub fps: FrameRate,
+    pub duration: f64,
+    pub images: Vec<ImageScenario>,
+    #[serde(default, skip_serializing_if = "Background::is_black")]
+    pub background: Background,
+}
+
+#[derive(Deserialize)]
+struct ImageScenarioFile {
+    image: String,
+    points: Option<Vec<ScenarioPoint>>,
+    tracks: Option<ImageTracks>,
+    #[serde(default)]
+    focus: Option<FocusRegion>,
+    #[serde(default)]
+    transition: Option<Transition>,
+}
+
+#[derive(Deserialize)]
+struct ScenarioFile {
+    #[serde(default = "default_version")]
+    version: u32,
     width: usize,
     height: usize,
-    length: usize,
+    length: Option<usize>,
+    fps: Option<FrameRate>,
+    duration: Option<f64>,
+    #[serde(default)]
+    interpolation: InterpolationSettings,
+    images: Vec<ImageScenarioFile>,
+    #[serde(default)]
+    background: Background,
+}
+
+impl TryFrom<ScenarioFile> for Scenario {
+    type Error = String;
+
+    fn try_from(file: ScenarioFile) -> Result<Self, Self::Error> {
+        if file.version == 0 || file.version > SCENARIO_VERSION {
+            return Err(format!("unsupported scenario version {}", file.version));
+        }
+        let mut images = Vec::with_capacity(file.images.len());
+        for image_file in file.images {
+            let image_scenario = match (image_file.points, image_file.tracks) {
+                (Some(points), None) => ImageScenario {
+                    focus: image_file.focus,
+                    transition: image_file.transition,
+                    ..ImageScenario::from_points(&image_file.image, &points, &file.interpolation)?
+                },
+                (None, Some(tracks)) => ImageScenario {
+                    image: image_file.image, tracks, focus: image_file.focus, transition: image_file.transition,
+                },
+                _ => return Err(format!("image {} must have either points or tracks", image_file.image)),
+            };
+            images.push(image_scenario);
+        }
+        let (fps, duration) = if file.version < 3 {
+            let length = file.length.ok_or_else(|| String::from("missing length"))?;
+            let fps = FrameRate::default();
+            let duration = length as f64 / fps.fps();
+            for image_scenario in &mut images {
+                image_scenario.tracks.scale_time(duration);
+            }
+            (fps, duration)
+        } else {
+            (file.fps.ok_or_else(|| String::from("missing fps"))?, file.duration.ok_or_else(|| String::from("missing duration"))?)
+        };
+        Ok(Scenario { version: SCENARIO_VERSION, width: file.width, height: file.height, fps, duration, images, background: file.background })
+    }
+}
+
+impl ScenarioPoint {
+    /// Transform from the source image to the frame: the anchor is moved to the offset point,
+    /// scaled and rotated around it.
+    pub fn matrix(&self) -> Matrix2d {
+        Matrix2d::translate(-self.anchor_x, -self.anchor_y)
+            .multiply(&Matrix2d::scale(self.scale))
+            .multiply(&Matrix2d::rotation(self.angle))
+            .multiply(&Matrix2d::translate(self.offset_x, self.offset_y))
+    }
+}
+
+impl InterpolationSettings {
+    pub fn all(interpolation: Interpolation) -> InterpolationSettings {
+        InterpolationSettings {
+            anchor: interpolation, offset: interpolation, scale: interpolation, angle: interpolation, alpha: interpolation,
+        }
+    }
+}
+
+impl ImageTracks {
+    fn scale_time(&mut self, factor: f64) {
+        for track in [&mut self.anchor_x, &mut self.anchor_y, &mut self.offset_x, &mut self.offset_y,
+            &mut self.scale, &mut self.angle, &mut self.alpha] {
+            track.scale_time(factor);
+        }
+    }
+
+    fn tracks(&self) -> [(&'static str, &Track); 7] {
+        [("anchor_x", &self.anchor_x), ("anchor_y", &self.anchor_y), ("offset_x", &self.offset_x), ("offset_y", &self.offset_y),
+            ("scale", &self.scale), ("angle", &self.angle), ("alpha", &self.alpha)]
+    }
+
+    fn validate(&self) -> Result<(), String> {
+        for (name, track) in self.tracks() {
+            track.validate().map_err(|e| format!("{} {}", name, e))?;
+        }
+        if let Some(key) = self.scale.keys.iter().find(|key| key.value <= 0.0) {
+            return Err(format!("scale keyframe at time {} has non-positive value {}", key.time, key.value));
+        }
+        if let Some(key) = self.alpha.keys.iter().find(|key| !(0.0..=1.0).contains(&key.value)) {
+            return Err(format!("alpha keyframe at time {} has value {} outside of 0..1", key.time, key.value));
+        }
+        if self.start_time() >= self.end_time() {
+            return Err(String::from("no track is animated"));
+        }
+        Ok(())
+    }
+
+    /// Tracks defining the time the image is shown: the animated ones, a constant track holds its value at any time.
+    fn timed_tracks(&self) -> Vec<&Track> {
+        let tracks: Vec<&Track> = self.tracks().into_iter().map(|(_, track)| track).collect();
+        if tracks.iter().any(|track| track.is_animated()) {
+            tracks.into_iter().filter(|track| track.is_animated()).collect()
+        } else {
+            tracks
+        }
+    }
+
+    pub fn start_time(&self) -> f64 {
+        self.timed_tracks().iter().map(|track| track.start_time()).fold(f64::INFINITY, f64::min)
+    }
+
+    pub fn end_time(&self) -> f64 {
+        self.timed_tracks().iter().map(|track| track.end_time()).fold(f64::NEG_INFINITY, f64::max)
+    }
+
+    /// Length of the hold between the first two alpha keyframes, or the last two at the `end`,
+    /// zero unless every track has the same value at both keyframes.
+    fn hold_time(&self, end: bool) -> f64 {
+        let keys = &self.alpha.keys;
+        if keys.len() < 3 {
+            return 0.0;
+        }
+        let (from, to) = if end { (keys[keys.len() - 2].time, keys[keys.len() - 1].time) } else { (keys[0].time, keys[1].time) };
+        if self.tracks().iter().all(|(_, track)| track.sample(from) == track.sample(to)) { to - from } else { 0.0 }
+    }
 }
 
 impl ImageScenario {
-    fn interpolate_points(&self, time: f64) -> Option<ScenarioPoint> {
-        if time < self.points[0].time {
-            None
-        } else if time >= self.points[self.points.len() - 1].time {
+    fn point_keys(points: &[ScenarioPoint], value: fn(&ScenarioPoint) -> f64, tangent: fn(&ScenarioTangents) -> Option<Tangent>) -> Vec<Keyframe> {
+        points.iter()
+            .map(|p| Keyframe { time: p.time, value: value(p), tangent: p.tangents.as_ref().and_then(tangent), easing: p.easing })
+            .collect()
+    }
+
+    /// Builds tracks keying every property at each of the points,
+    /// scale is interpolated in logarithmic space and angle along the shortest arc.
+    pub fn from_points(image: &str, points: &[ScenarioPoint], settings: &InterpolationSettings) -> Result<ImageScenario, String> {
+        if points.len() < 2 {
+            return Err(format!("image {} has {} keyframes, at least 2 required", image, points.len()));
+        }
+        let tracks = ImageTracks {
+            anchor_x: Track::new(settings.anchor, ImageScenario::point_keys(points, |p| p.anchor_x, |t| t.anchor_x)),
+            anchor_y: Track::new(settings.anchor, ImageScenario::point_keys(points, |p| p.anchor_y, |t| t.anchor_y)),
+            offset_x: Track::new(settings.offset, ImageScenario::point_keys(points, |p| p.offset_x, |t| t.offset_x)),
+            offset_y: Track::new(settings.offset, ImageScenario::point_keys(points, |p| p.offset_y, |t| t.offset_y)),
+            scale: Track::new(settings.scale, ImageScenario::point_keys(points, |p| p.scale, |t| t.scale))
+                .with_space(ValueSpace::Logarithmic),
+            angle: Track::new(settings.angle, ImageScenario::point_keys(points, |p| p.angle, |t| t.angle))
+                .with_space(ValueSpace::ShortestArc),
+            alpha: Track::new(settings.alpha, ImageScenario::point_keys(points, |p| p.alpha, |t| t.alpha)),
+        };
+        Ok(ImageScenario { image: String::from(image), tracks, focus: None, transition: None })
+    }
+
+    fn validate(&self) -> Result<(), String> {
+        if self.image.is_empty() {
+            return Err(String::from("image path is empty"));
+        }
+        self.tracks.validate().map_err(|e| format!("image {}: {}", self.image, e))
+    }
+
+    pub(crate) fn sample(&self, time: f64) -> Option<ScenarioPoint> {
+        if time < self.tracks.start_time() || time >= self.tracks.end_time() {
             None
         } else {
-            let mut result = ScenarioPoint {time, anchor_x: 0.0, anchor_y: 0.0, offset_x: 0.0, offset_y: 0.0, scale: 0.0, angle: 0.0, alpha: 0.0};
-            for i in 0..self.points.len() {
-                let pi = &self.points[i];
-                let mut l = 1.0;
-                for j in 0..self.points.len() {
-                    if i != j {
-                        let pj = &self.points[j];
-                        l *= (time - pj.time) / (pi.time - pj.time);
-                    }
-                }
-                result.anchor_x += l * pi.anchor_x;
-                result.anchor_y += l * pi.anchor_y;
-                result.offset_x += l * pi.offset_x;
-                result.offset_y += l * pi.offset_y;
-                result.scale += l * pi.scale;
-                result.angle += l * pi.angle;
-                result.alpha += l * pi.alpha;
-            }
-            if result.alpha > 1.0 {result.alpha = 1.0;} else if result.alpha < 0.0 {result.alpha = 0.0;}
-            Some(result)
+            Some(ScenarioPoint {
+                time,
+                anchor_x: self.tracks.anchor_x.sample(time),
+                anchor_y: self.tracks.anchor_y.sample(time),
+                offset_x: self.tracks.offset_x.sample(time),
+                offset_y: self.tracks.offset_y.sample(time),
+                scale: self.tracks.scale.sample(time),
+                angle: self.tracks.angle.sample(time),
+                alpha: self.tracks.alpha.sample(time).clamp(0.0, 1.0),
+                tangents: None,
+                easing: Easing::Linear,
+            })
         }
     }
 }
 
 impl Scenario {
-    pub fn generate_scenario(images: &Vec<String>, images_map: &HashMap<String, RenkiImage>,
-                             width: usize, height: usize, length: usize) -> Scenario {
-        let mut images_scenarios = Vec::with_capacity(images.len());
-        for image_index in 0..images.len() {
-            let image_filename = &images[image_index];
-            let image = images_map.get(image_filename).unwrap();
-            let fit_scale = height as f64 / image.height as f64;
-
-            let anchor_point_y = image.height as f64 * 0.5;
-            let anchor_point_left = width as f64 / fit_scale * 0.6;
-            let anchor_point_right = image.width as f64 - width as f64 / fit_scale * 0.6;
-            let (anchor_point0, anchor_point1) = if image_index % 2 == 0 {
-                (anchor_point_left, anchor_point_right)
-            } else {
-                (anchor_point_right, anchor_point_left)
-            };
+    pub fn from_json(json: &str) -> Result<Scenario, String> {
+        let scenario: Scenario = serde_json::from_str(json).map_err(|e| e.to_string())?;
+        scenario.validate()?;
+        Ok(scenario)
+    }
 
-            let offset_point_y = height as f64 * 0.5;
-            let offset_point_left = width as f64 * 0.5;
-            let offset_point_right = width as f64 * 0.5;
-            let (offset_point0, offset_point1) = if image_index % 2 == 0 {
-                (offset_point_left, offset_point_right)
-            } else {
-                (offset_point_right, offset_point_left)
-            };
+    pub fn load(path: &str) -> Result<Scenario, String> {
+        let json = fs::read_to_string(path).map_err(|e| format!("Failed to read scenario {}: {}", path, e))?;
+        Scenario::from_json(&json).map_err(|e| format!("Invalid scenario {}: {}", path, e))
+    }
 
-            let start_time = image_index as f64 / images.len() as f64 / (1.0 + 0.25 / images.len() as f64);
-            let end_time = (image_index + 1) as f64 / images.len() as f64 / (1.0 + 0.25 / images.len() as f64);
-            let duration = (end_time - start_time) * 1.25;
-
-            let mut points = Vec::new();
-            points.push(ScenarioPoint {
-                time: start_time,
-                anchor_x: anchor_point0, anchor_y: anchor_point_y,
-                offset_x: offset_point0, offset_y: offset_point_y,
-                angle: 0.075, scale: fit_scale * 1.5, alpha: 0.0});
-            points.push(ScenarioPoint {
-                time: start_time + duration * 0.2,
-                anchor_x: anchor_point0, anchor_y: anchor_point_y,
-                offset_x: offset_point0, offset_y: offset_point_y,
-                angle: 0.0, scale: fit_scale * 1.0, alpha: 0.88});
-            points.push(ScenarioPoint {
-                time: start_time + duration * 0.5,
-                anchor_x: (anchor_point0 + anchor_point1) * 0.5, anchor_y: anchor_point_y,
-                offset_x: (offset_point0 + offset_point1) * 0.5, offset_y: offset_point_y,
-                angle: 0.0, scale: fit_scale * 1.125, alpha: 1.0});
-            points.push(ScenarioPoint {
-                time: start_time + duration * 0.8,
-                anchor_x: anchor_point1, anchor_y: anchor_point_y,
-                offset_x: offset_point1, offset_y: offset_point_y,
-                angle: 0.0, scale: fit_scale * 1.0, alpha: 0.88});
-            points.push(ScenarioPoint {
-                time: start_time + duration,
-                anchor_x: anchor_point1, anchor_y: anchor_point_y,
-                offset_x: offset_point1, offset_y: offset_point_y,
-                angle: -0.066, scale: fit_scale * 1.5, alpha: 0.0});
-
-            let image_scenario = ImageScenario {image: image_filename.clone(), points};
-            images_scenarios.push(image_scenario);
-        }
-        Scenario {images: images_scenarios, width, height, length}
-    }
-
-    fn render_frame(&self, time: f64, images_map: &HashMap<String, RenkiImage>) -> RenkiImage {
-        let channel_size = self.width * self.height;
-        let mut data = Vec::new();
-        for _channel_index in 0..3 {
-            data.push(vec![0_f32; channel_size]);
+    pub fn to_json(&self) -> Result<String, String> {
+        serde_json::to_string_pretty(self).map_err(|e| e.to_string())
+    }
+
+    pub fn save(&self, path: &str) -> Result<(), String> {
+        let json = self.to_json()?;
+        fs::write(path, json).map_err(|e| format!("Failed to write scenario {}: {}", path, e))
+    }
+
+    pub fn validate(&self) -> Result<(), String> {
+        if self.width == 0 || self.height == 0 {
+            return Err(format!("frame size {}x{} is empty", self.width, self.height));
+        }
+        if !self.duration.is_finite() || self.duration <= 0.0 {
+            return Err(format!("invalid duration {}", self.duration));
+        }
+        if self.frame_count() == 0 {
+            return Err(String::from("duration must be at least one frame"));
+        }
+        if self.images.is_empty() {
+            return Err(String::from("scenario has no images"));
+        }
+        for image_scenario in &self.images {
+            image_scenario.validate()?;
+        }
+        self.background.validate()?;
+        if self.images[0].transition.is_some() {
+            return Err(format!("image {} has a transition but no previous image", self.images[0].image));
+        }
+        for pair in self.images.windows(2) {
+            if let Some(transition) = &pair[1].transition {
+                transition.validate().map_err(|e| format!("image {}: {}", pair[1].image, e))?;
+                if pair[1].tracks.start_time() >= pair[0].tracks.end_time() {
+                    return Err(format!("image {} has a transition but does not overlap the previous image", pair[1].image));
+                }
+            }
+        }
+        Ok(())
+    }
+
+    pub fn frame_count(&self) -> usize {
+        self.fps.frame_count(self.duration)
+    }
+
+    /// Time each image is shown, including its transitions, in seconds. The lead-in hold of the first image and
+    /// the lead-out hold of the last one are left out, generating with these durations and the same lead times
+    /// repeats the timing.
+    pub fn image_durations(&self) -> Vec<f64> {
+        let last = self.images.len().saturating_sub(1);
+        self.images.iter().enumerate()
+            .map(|(index, image_scenario)| {
+                let tracks = &image_scenario.tracks;
+                let mut duration = tracks.end_time() - tracks.start_time();
+                if index == 0 {
+                    duration -= tracks.hold_time(false);
+                }
+                if index == last {
+                    duration -= tracks.hold_time(true);
+                }
+                duration
+            })
+            .collect()
+    }
+
+    /// Focus regions of the images, keyed by the image paths as written in the scenario.
+    pub fn focus_regions(&self) -> HashMap<String, FocusRegion> {
+        self.images.iter()
+            .filter_map(|image_scenario| image_scenario.focus.clone().map(|focus| (image_scenario.image.clone(), focus)))
+            .collect()
+    }
+
+    pub fn image_files(&self) -> Vec<String> {
+        let mut files: Vec<String> = Vec::new();
+        for image_scenario in &self.images {
+            let matte = image_scenario.transition.as_ref().and_then(|transition| transition.matte_image());
+            for file in std::iter::once(image_scenario.image.as_str()).chain(matte) {
+                if !files.iter().any(|known| known == file) {
+                    files.push(file.to_string());
+                }
+            }
+        }
+        if let Some(file) = self.background.image() {
+            if !files.iter().any(|known| known == file) {
+                files.push(file.to_string());
+            }
         }
-        let mut result = RenkiImage { width: self.width, height: self.height, channels: data, alpha: Vec::new() };
+        files
+    }
+
+    /// Replaces every image path of the scenario, including the matte and background images, by `map` of it.
+    pub fn map_image_files(&mut self, map: impl Fn(&str) -> String) {
+        for image_scenario in &mut self.images {
+            image_scenario.image = map(&image_scenario.image);
+            if let Some(TransitionKind::LumaMatte { image }) = image_scenario.transition.as_mut().map(|transition| &mut transition.kind) {
+                *image = map(image);
+            }
+        }
+        if let Background::Image { image } = &mut self.background {
+            *image = map(image);
+        }
+    }
+
+    /// Transition into the image at `image_index` and its progress, `None` outside of the transition.
+    fn transition_at(&self, image_index: usize, time: f64) -> Option<(&Transition, f64)> {
+        let transition = self.images.get(image_index)?.transition.as_ref()?;
+        let start_time = self.images[image_index].tracks.start_time();
+        let end_time = self.images[image_index.checked_sub(1)?].tracks.end_time();
+        if time < start_time || time >= end_time {
+            None
+        } else {
+            Some((transition, transition.progress(time, start_time, end_time)))
+        }
+    }
+
+    pub(crate) fn prepare_background(&self, images_map: &HashMap<String, RenkiImage>,
+                                     options: &RenderOptions) -> Result<BackgroundLayers, String> {
+        let images: Vec<String> = self.images.iter().map(|image_scenario| image_scenario.image.clone()).collect();
+        let mut layers = self.background.prepare(self.width, self.height, &images, images_map, options.linear)?;
+        if options.transparent {
+            let size = self.width * self.height;
+            layers.base = RenkiImage::new(self.width, self.height, vec![vec![0_f32; size]; layers.base.channels.len()], vec![0_f32; size]);
+        }
+        Ok(layers)
+    }
+
+    pub(crate) fn render_frame(&self, time: f64, images_map: &HashMap<String, RenkiImage>, background: &BackgroundLayers,
+                               options: &RenderOptions) -> Result<RenkiImage, String> {
+        let channel_size = self.width * self.height;
+        let mut result = background.base.clone();
         for scenario_index in 0..self.images.len() {
             let image_scenario = &self.images[scenario_index];
-            let image = images_map.get(&image_scenario.image).expect("Failed to find image");
-
-            let point = image_scenario.interpolate_points(time);
-            if point.is_some() {
-                let point = point.unwrap();
-                let matrix = Matrix2d::translate(-point.anchor_x, -point.anchor_y)
-                    .multiply(&Matrix2d::scale(point.scale))
-                    .multiply(&Matrix2d::rotation(point.angle))
-                    .multiply(&Matrix2d::translate(point.offset_x, point.offset_y));
-                let image = image.transform(&matrix, self.width, self.height, point.alpha);
-                result = result.blend(&image);
+            let image = images_map.get(&image_scenario.image).ok_or_else(|| format!("image {} is not loaded", image_scenario.image))?;
+
+            let incoming = self.transition_at(scenario_index, time);
+            let mut effects = Vec::new();
+            if let Some((transition, progress)) = incoming {
+                let matte = match transition.matte_image() {
+                    Some(matte) => Some(images_map.get(matte).ok_or_else(|| format!("matte image {} is not loaded", matte))?),
+                    None => None,
+                };
+                effects.push(transition.incoming(progress, self.width, self.height, matte, options.linear));
+            }
+            if let Some((transition, progress)) = self.transition_at(scenario_index + 1, time) {
+                effects.push(transition.outgoing(progress, self.width, self.height));
+            }
+
+            if let Some(point) = image_scenario.sample(time) {
+                let mut matrix = point.matrix();
+                let mut alpha = if effects.is_empty() { point.alpha } else { 1.0 };
+                for effect in &effects {
+                    if let Some(effect_matrix) = &effect.matrix {
+                        matrix = matrix.multiply(effect_matrix);
+                    }
+                    alpha *= effect.alpha;
+                }
+                // layers faded out do not change the frame
+                if alpha > 0.0 {
+                    let (source, matrix) = if options.mipmaps { image.mipmap_level(matrix) } else { (image, matrix) };
+                    let mut image = source.transform_threaded(&matrix, self.width, self.height, alpha, options.resampler, options.tile_threads());
+                    let mut pad = background.pad(&image_scenario.image, alpha);
+                    for mask in effects.iter().filter_map(|effect| effect.mask.as_ref()) {
+                        image.alpha.iter_mut().zip(mask).for_each(|(alpha, mask)| *alpha *= mask);
+                        if let Some(pad) = pad.as_mut() {
+                            pad.alpha.iter_mut().zip(mask).for_each(|(alpha, mask)| *alpha *= mask);
+                        }
+                    }
+                    if let Some(pad) = pad {
+                        result = result.blend(&pad);
+                    }
+                    result = result.blend(&image);
+                }
+            }
+            if let Some((color, opacity)) = incoming.and_then(|(transition, progress)| transition.overlay(progress)) {
+                let color = if options.linear { color_to_linear(&color) } else { color };
+                let channels = color.iter().map(|value| vec![*value; channel_size]).collect();
+                let overlay = RenkiImage::new(self.width, self.height, channels, vec![opacity as f32; channel_size]);
+                result = result.blend(&overlay);
             }
         }
-        result
+        Ok(result)
     }
 
-    pub fn render(&self, images_map: &HashMap<String, RenkiImage>, frames_prefix: &str) {
-        for frame_index in 0..self.length {
-            let time = frame_index as f64 / self.length as f64;
-            let frame = self.render_frame(time, images_map);
-            let frame_name = format!("{}frame{:04}.png", frames_prefix, frame_index);
-            frame.save(frame_name.as_str());
-            println!("Progress {}%", (time * 100.0) as i32);
+    pub fn render(&self, images_map: &HashMap<String, RenkiImage>, frames_prefix: &str, options: &RenderOptions) -> Result<(), String> {
+        let frame_count = self.frame_count();
+        let background = self.prepare_background(images_map, options)?;
+        let render_to_file = |frame_index: usize| -> Result<(), String> {
+            let time = self.fps.frame_time(frame_index);
+            let frame = self.render_frame(time, images_map, &background, options)?;
+            let frame_name = format!("{}frame{:04}.{}", frames_prefix, frame_index, options.output.extension());
+            frame.save_frame(frame_name.as_str(), options, frame_index);
+            Ok(())
+        };
+        let threads = if options.tiles { 1 } else { options.thread_count().min(frame_count) };
+        if threads <= 1 {
+            for frame_index in 0..frame_count {
+                render_to_file(frame_index)?;
+                println!("Progress {}%", frame_index * 100 / frame_count);
+            }
+            return Ok(());
         }
+
+        // workers take the next frame index, progress is reported in frame order as frames complete,
+        // the first failure stops the workers
+        let next_frame = AtomicUsize::new(0);
+        let (sender, receiver) = mpsc::channel();
+        thread::scope(|scope| {
+            for _thread_index in 0..threads {
+                let sender = sender.clone();
+                let (next_frame, render_to_file) = (&next_frame, &render_to_file);
+                scope.spawn(move || loop {
+                    let frame_index = next_frame.fetch_add(1, Ordering::Relaxed);
+                    if frame_index >= frame_count {
+                        break;
+                    }
+                    let result = render_to_file(frame_index).map(|_| frame_index);
+                    if result.is_err() {
+                        next_frame.store(frame_count, Ordering::Relaxed);
+                    }
+                    if sender.send(result).is_err() {
+                        // rendering stopped on a failed frame
+                        break;
+                    }
+                });
+            }
+            drop(sender);
+            let mut rendered = vec![false; frame_count];
+            let mut reported = 0;
+            for result in receiver {
+                rendered[result?] = true;
+                while reported < frame_count && rendered[reported] {
+                    println!("Progress {}%", reported * 100 / frame_count);
+                    reported += 1;
+                }
+            }
+            Ok(())
+        })
     }
 }
\ No newline at end of file
@@ -0,0 +1,114 @@
+use std::f64::consts::PI;
+use serde::{Deserialize, Serialize};
+use crate::interpolation::{Easing, Interpolation, Keyframe};
+
+/// Space the keyframe values are interpolated in.
+/// `logarithmic` gives perceptually uniform zooms for scale tracks, values must be positive.
+/// `shortest_arc` treats values as angles in radians and turns from each keyframe to the next one
+/// along the shorter direction, so 3.0 to -3.0 passes through pi instead of zero.
+/// Bezier tangents are given in the interpolation space.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ValueSpace {
+    #[default]
+    Linear,
+    Logarithmic,
+    ShortestArc,
+}
+
+/// Keyframes of a single animated property.
+/// Before the first and after the last keyframe the track holds the boundary value.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Track {
+    #[serde(default)]
+    pub interpolation: Interpolation,
+    #[serde(default, skip_serializing_if = "ValueSpace::is_linear")]
+    pub space: ValueSpace,
+    pub keys: Vec<Keyframe>,
+}
+
+impl ValueSpace {
+    pub fn is_linear(&self) -> bool {
+        *self == ValueSpace::Linear
+    }
+}
+
+impl Track {
+    pub fn new(interpolation: Interpolation, keys: Vec<Keyframe>) -> Track {
+        Track { interpolation, space: ValueSpace::Linear, keys }
+    }
+
+    pub fn with_space(self, space: ValueSpace) -> Track {
+        Track { space, ..self }
+    }
+
+    pub fn constant(value: f64) -> Track {
+        Track::new(Interpolation::Linear, vec![Keyframe { time: 0.0, value, tangent: None, easing: Easing::Linear }])
+    }
+
+    pub fn start_time(&self) -> f64 {
+        self.keys[0].time
+    }
+
+    pub fn end_time(&self) -> f64 {
+        self.keys[self.keys.len() - 1].time
+    }
+
+    /// Stretches the track in time, tangents are adjusted to keep the curve shape.
+    pub fn scale_time(&mut self, factor: f64) {
+        for key in &mut self.keys {
+            key.time *= factor;
+            if let Some(tangent) = key.tangent.as_mut() {
+                tangent.incoming /= factor;
+                tangent.outgoing /= factor;
+            }
+        }
+    }
+
+    pub fn is_animated(&self) -> bool {
+        self.keys.len() > 1
+    }
+
+    fn space_keys(&self) -> Vec<Keyframe> {
+        let mut keys = self.keys.clone();
+        match self.space {
+            ValueSpace::Linear => {}
+            ValueSpace::Logarithmic => keys.iter_mut().for_each(|key| key.value = key.value.ln()),
+            ValueSpace::ShortestArc => {
+                for i in 1..keys.len() {
+                    let delta = self.keys[i].value - self.keys[i - 1].value;
+                    keys[i].value = keys[i - 1].value + delta - 2.0 * PI * (delta / (2.0 * PI)).round();
+                }
+            }
+        }
+        keys
+    }
+
+    pub fn sample(&self, time: f64) -> f64 {
+        match self.space {
+            ValueSpace::Linear => self.interpolation.interpolate(&self.keys, time),
+            ValueSpace::Logarithmic => self.interpolation.interpolate(&self.space_keys(), time).exp(),
+            ValueSpace::ShortestArc => self.interpolation.interpolate(&self.space_keys(), time),
+        }
+    }
+
+    pub fn validate(&self) -> Result<(), String> {
+        if self.keys.is_empty() {
+            return Err(String::from("track has no keyframes"));
+        }
+        for key in &self.keys {
+            if !key.time.is_finite() || !key.value.is_finite() {
+                return Err(format!("keyframe at time {} has non-finite values", key.time));
+            }
+        }
+        if self.space == ValueSpace::Logarithmic && self.keys.iter().any(|key| key.value <= 0.0) {
+            return Err(String::from("logarithmic track has non-positive values"));
+        }
+        for pair in self.keys.windows(2) {
+            if pair[1].time <= pair[0].time {
+                return Err(format!("keyframe times {} and {} are not increasing", pair[0].time, pair[1].time));
+            }
+        }
+        Ok(())
+    }
+}
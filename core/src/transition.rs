@@ -0,0 +1,234 @@
+use std::str::FromStr;
+use serde::{Deserialize, Serialize};
+use crate::color::linear_to_srgb;
+use crate::interpolation::Easing;
+use crate::matrix::Matrix2d;
+use crate::renki_image::RenkiImage;
+
+/// Direction a transition moves in on the frame.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Direction {
+    #[default]
+    Left,
+    Right,
+    Up,
+    Down,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum TransitionKind {
+    /// The incoming image fades in over the outgoing one.
+    Crossfade,
+    /// The outgoing image fades to a color, then the incoming one fades in from it.
+    /// `color` is `[r, g, b]` from 0 to 255, black by default.
+    Dip {
+        #[serde(default)]
+        color: [f32; 3],
+    },
+    /// An edge moving across the frame reveals the incoming image.
+    Wipe {
+        #[serde(default)]
+        direction: Direction,
+    },
+    /// The incoming image slides in over the outgoing one, with `push` the outgoing one slides out ahead of it.
+    Slide {
+        #[serde(default)]
+        direction: Direction,
+        #[serde(default)]
+        push: bool,
+    },
+    /// A circle growing from the frame center reveals the incoming image.
+    Iris,
+    /// The outgoing image zooms in towards the viewer while the incoming one fades in zooming up to its size.
+    ZoomThrough,
+    /// A grayscale image stretched over the frame reveals the incoming image from its dark to its light areas.
+    LumaMatte {
+        image: String,
+    },
+}
+
+/// Transition from the previous image to the image it belongs to, run while the two images overlap.
+/// It replaces the crossfade given by the alpha tracks: both images are drawn opaque during the overlap
+/// and the transition decides which parts of them are visible.
+/// `easing` remaps the transition progress, `softness` is the width of the wipe, iris and matte edges
+/// as a part of the transition.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Transition {
+    #[serde(flatten)]
+    pub kind: TransitionKind,
+    #[serde(default, skip_serializing_if = "Easing::is_linear")]
+    pub easing: Easing,
+    #[serde(default = "default_softness")]
+    pub softness: f64,
+}
+
+fn default_softness() -> f64 {
+    0.05
+}
+
+/// How a transition draws one of its images: `matrix` is applied in the frame after the image transform,
+/// `mask` multiplies the image alpha per frame pixel.
+pub(crate) struct LayerEffect {
+    pub matrix: Option<Matrix2d>,
+    pub alpha: f64,
+    pub mask: Option<Vec<f32>>,
+}
+
+impl LayerEffect {
+    fn opaque() -> LayerEffect {
+        LayerEffect { matrix: None, alpha: 1.0, mask: None }
+    }
+}
+
+impl Direction {
+    fn vector(&self) -> (f64, f64) {
+        match self {
+            Direction::Left => (-1.0, 0.0),
+            Direction::Right => (1.0, 0.0),
+            Direction::Up => (0.0, -1.0),
+            Direction::Down => (0.0, 1.0),
+        }
+    }
+}
+
+/// Moves and scales the frame around its center.
+fn frame_matrix(width: usize, height: usize, scale: f64, dx: f64, dy: f64) -> Matrix2d {
+    let (center_x, center_y) = (width as f64 * 0.5, height as f64 * 0.5);
+    Matrix2d::translate(-center_x, -center_y)
+        .multiply(&Matrix2d::scale(scale))
+        .multiply(&Matrix2d::translate(center_x + dx, center_y + dy))
+}
+
+impl Transition {
+    pub fn new(kind: TransitionKind) -> Transition {
+        Transition { kind, easing: Easing::Linear, softness: default_softness() }
+    }
+
+    /// Grayscale image of a luma matte transition.
+    pub fn matte_image(&self) -> Option<&str> {
+        match &self.kind {
+            TransitionKind::LumaMatte { image } => Some(image),
+            _ => None,
+        }
+    }
+
+    pub fn validate(&self) -> Result<(), String> {
+        if !self.softness.is_finite() || self.softness < 0.0 {
+            return Err(format!("invalid transition softness {}", self.softness));
+        }
+        match &self.kind {
+            TransitionKind::LumaMatte { image } if image.is_empty() => Err(String::from("luma matte image path is empty")),
+            _ => Ok(()),
+        }
+    }
+
+    /// Transition progress from 0.0 to 1.0 at `time` of the transition running from `start_time` to `end_time`.
+    pub(crate) fn progress(&self, time: f64, start_time: f64, end_time: f64) -> f64 {
+        self.easing.apply(((time - start_time) / (end_time - start_time)).clamp(0.0, 1.0))
+    }
+
+    /// Mask revealing the parts of the frame where `position` is behind the edge moved to `progress`.
+    fn edge_mask(&self, progress: f64, width: usize, height: usize, position: impl Fn(f64, f64) -> f64) -> Vec<f32> {
+        let edge = progress * (1.0 + self.softness);
+        let mut mask = Vec::with_capacity(width * height);
+        for y in 0..height {
+            for x in 0..width {
+                let behind = edge - position(x as f64 + 0.5, y as f64 + 0.5);
+                let value = if self.softness > 0.0 { behind / self.softness } else if behind > 0.0 { 1.0 } else { 0.0 };
+                mask.push(value.clamp(0.0, 1.0) as f32);
+            }
+        }
+        mask
+    }
+
+    pub(crate) fn outgoing(&self, progress: f64, width: usize, height: usize) -> LayerEffect {
+        match &self.kind {
+            TransitionKind::Dip { .. } => LayerEffect { alpha: if progress < 0.5 { 1.0 } else { 0.0 }, ..LayerEffect::opaque() },
+            TransitionKind::Slide { direction, push: true } => {
+                let (dx, dy) = direction.vector();
+                let matrix = frame_matrix(width, height, 1.0, dx * progress * width as f64, dy * progress * height as f64);
+                LayerEffect { matrix: Some(matrix), ..LayerEffect::opaque() }
+            }
+            TransitionKind::ZoomThrough => {
+                LayerEffect { matrix: Some(frame_matrix(width, height, 1.0 + progress, 0.0, 0.0)), ..LayerEffect::opaque() }
+            }
+            _ => LayerEffect::opaque(),
+        }
+    }
+
+    /// `matte` is the loaded image of a luma matte transition, `linear` if it was converted to linear light.
+    pub(crate) fn incoming(&self, progress: f64, width: usize, height: usize, matte: Option<&RenkiImage>, linear: bool) -> LayerEffect {
+        let (frame_width, frame_height) = (width as f64, height as f64);
+        match &self.kind {
+            TransitionKind::Crossfade => LayerEffect { alpha: progress, ..LayerEffect::opaque() },
+            TransitionKind::Dip { .. } => LayerEffect { alpha: if progress < 0.5 { 0.0 } else { 1.0 }, ..LayerEffect::opaque() },
+            TransitionKind::Wipe { direction } => {
+                let mask = self.edge_mask(progress, width, height, |x, y| match direction {
+                    Direction::Right => x / frame_width,
+                    Direction::Left => 1.0 - x / frame_width,
+                    Direction::Down => y / frame_height,
+                    Direction::Up => 1.0 - y / frame_height,
+                });
+                LayerEffect { mask: Some(mask), ..LayerEffect::opaque() }
+            }
+            TransitionKind::Slide { direction, .. } => {
+                let (dx, dy) = direction.vector();
+                let remaining = 1.0 - progress;
+                let matrix = frame_matrix(width, height, 1.0, -dx * remaining * frame_width, -dy * remaining * frame_height);
+                LayerEffect { matrix: Some(matrix), ..LayerEffect::opaque() }
+            }
+            TransitionKind::Iris => {
+                let (center_x, center_y) = (frame_width * 0.5, frame_height * 0.5);
+                let radius = center_x.hypot(center_y);
+                let mask = self.edge_mask(progress, width, height, |x, y| (x - center_x).hypot(y - center_y) / radius);
+                LayerEffect { mask: Some(mask), ..LayerEffect::opaque() }
+            }
+            TransitionKind::ZoomThrough => LayerEffect {
+                matrix: Some(frame_matrix(width, height, 1.0 / (2.0 - progress), 0.0, 0.0)),
+                alpha: progress,
+                mask: None,
+            },
+            TransitionKind::LumaMatte { .. } => {
+                let mask = match matte {
+                    Some(matte) => self.edge_mask(progress, width, height, |x, y| {
+                        let matte_x = ((x / frame_width * matte.width as f64) as usize).min(matte.width - 1);
+                        let matte_y = ((y / frame_height * matte.height as f64) as usize).min(matte.height - 1);
+                        let index = matte_y * matte.width + matte_x;
+                        let luma = if matte.channels.len() >= 3 {
+                            0.299 * matte.channels[0][index] + 0.587 * matte.channels[1][index] + 0.114 * matte.channels[2][index]
+                        } else {
+                            matte.channels[0][index]
+                        };
+                        // the matte reveals by perceived brightness
+                        let luma = if linear { linear_to_srgb(luma) } else { luma };
+                        luma as f64 / 255.0
+                    }),
+                    None => vec![progress as f32; width * height],
+                };
+                LayerEffect { mask: Some(mask), ..LayerEffect::opaque() }
+            }
+        }
+    }
+
+    /// Color drawn over both images and its opacity.
+    pub(crate) fn overlay(&self, progress: f64) -> Option<([f32; 3], f64)> {
+        match &self.kind {
+            TransitionKind::Dip { color } => Some((*color, 1.0 - (2.0 * progress - 1.0).abs())),
+            _ => None,
+        }
+    }
+}
+
+impl FromStr for Transition {
+    type Err = String;
+
+    /// A transition as JSON, or the name of a transition kind with default parameters, e.g. `wipe`.
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        let json = if value.trim_start().starts_with('{') { value.to_string() } else { format!("{{\"type\": \"{}\"}}", value) };
+        let transition: Transition = serde_json::from_str(&json).map_err(|e| format!("invalid transition {}: {}", value, e))?;
+        transition.validate()?;
+        Ok(transition)
+    }
+}